@@ -0,0 +1,191 @@
+// Criterion benchmarks for the library's table operations, each run against an
+// in-memory and an on-disk database. Run with "cargo bench"; criterion compares
+// every run with the previous one and reports whether the change is significant.
+// The library functions print as they run, so their messages are interleaved
+// with criterion's; the results are also saved under target/criterion.
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use rusqlite::Connection;
+use sqlite::audit::{create_audit_table, AUDIT_TABLE};
+use sqlite::generate::{generate_rows, DEFAULT_SEED};
+use sqlite::{
+    create_exec, create_table, delete_exec, drop_table, load_data_from_csv, read_exec, update_exec,
+    UpdateFields,
+};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+// Rows in the CSVs loaded by the load benchmarks
+const LOAD_SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+
+// Rows in the table the read, update and delete benchmarks work on
+const TABLE_ROWS: usize = 10_000;
+
+const TABLE: &str = "bench_table";
+
+#[derive(Clone, Copy)]
+enum Storage {
+    Memory,
+    Disk,
+}
+
+impl Storage {
+    fn name(self) -> &'static str {
+        match self {
+            Storage::Memory => "memory",
+            Storage::Disk => "disk",
+        }
+    }
+
+    // A fresh, empty database
+    fn open(self) -> Connection {
+        match self {
+            Storage::Memory => Connection::open_in_memory().unwrap(),
+            Storage::Disk => {
+                let path = bench_dir().join("bench.db");
+                let _ = fs::remove_file(&path);
+                Connection::open(path).unwrap()
+            }
+        }
+    }
+}
+
+const STORAGES: [Storage; 2] = [Storage::Memory, Storage::Disk];
+
+fn bench_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join("sqlite_benches");
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// A synthetic CSV of `rows` rows, written once and reused across runs
+fn csv_path(rows: usize) -> String {
+    let path = bench_dir().join(format!("fifa_{}.csv", rows));
+    if !path.exists() {
+        let mut out = BufWriter::new(File::create(&path).unwrap());
+        generate_rows(rows, DEFAULT_SEED, &mut out).unwrap();
+    }
+    path.to_string_lossy().into_owned()
+}
+
+// Empty the audit table, so entries written by earlier iterations do not slow
+// down later ones
+fn reset_audit_log(conn: &Connection) {
+    create_audit_table(conn).unwrap();
+    conn.execute(&format!("DELETE FROM {}", AUDIT_TABLE), [])
+        .unwrap();
+}
+
+// A database holding TABLE with TABLE_ROWS rows
+fn loaded(storage: Storage) -> Connection {
+    let conn = storage.open();
+    create_table(&conn, TABLE).unwrap();
+    load_data_from_csv(&conn, TABLE, &csv_path(TABLE_ROWS)).unwrap();
+    conn
+}
+
+fn bench_create_table(c: &mut Criterion) {
+    let mut group = c.benchmark_group("create_table");
+    for storage in STORAGES {
+        let conn = storage.open();
+        group.bench_function(storage.name(), |b| {
+            b.iter(|| {
+                create_table(&conn, TABLE).unwrap();
+                drop_table(&conn, TABLE).unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_load_data_from_csv(c: &mut Criterion) {
+    let mut group = c.benchmark_group("load_data_from_csv");
+    group.sample_size(10);
+    for rows in LOAD_SIZES {
+        let path = csv_path(rows);
+        group.throughput(Throughput::Elements(rows as u64));
+        for storage in STORAGES {
+            let conn = storage.open();
+            group.bench_with_input(BenchmarkId::new(storage.name(), rows), &path, |b, path| {
+                b.iter_batched(
+                    || {
+                        drop_table(&conn, TABLE).unwrap();
+                        create_table(&conn, TABLE).unwrap();
+                        reset_audit_log(&conn);
+                    },
+                    |()| load_data_from_csv(&conn, TABLE, path).unwrap(),
+                    BatchSize::PerIteration,
+                )
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_read_exec(c: &mut Criterion) {
+    let mut group = c.benchmark_group("read_exec");
+    for storage in STORAGES {
+        let conn = loaded(storage);
+        group.bench_function(storage.name(), |b| {
+            b.iter(|| read_exec(&conn, TABLE).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_update_exec(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update_exec");
+    for storage in STORAGES {
+        let conn = loaded(storage);
+        let mut share = 0.0;
+        group.bench_function(storage.name(), |b| {
+            b.iter_batched(
+                || {
+                    reset_audit_log(&conn);
+                    share += 0.1;
+                    UpdateFields {
+                        new_population_share: Some(Some(share)),
+                        ..UpdateFields::default()
+                    }
+                },
+                |fields| update_exec(&conn, TABLE, (TABLE_ROWS / 2) as i32, fields).unwrap(),
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_delete_exec(c: &mut Criterion) {
+    let mut group = c.benchmark_group("delete_exec");
+    for storage in STORAGES {
+        let conn = loaded(storage);
+        group.bench_function(storage.name(), |b| {
+            b.iter_batched(
+                // Each iteration deletes a record inserted for it
+                || {
+                    reset_audit_log(&conn);
+                    create_exec(&conn, TABLE, "BenchCountry", "UEFA", 0.1, 0.2, 0.3).unwrap();
+                    // last_insert_rowid() would be the audit entry's id
+                    conn.query_row(&format!("SELECT MAX(id) FROM {}", TABLE), [], |row| {
+                        row.get::<_, i32>(0)
+                    })
+                    .unwrap()
+                },
+                |id| delete_exec(&conn, TABLE, id).unwrap(),
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_create_table,
+    bench_load_data_from_csv,
+    bench_read_exec,
+    bench_update_exec,
+    bench_delete_exec
+);
+criterion_main!(benches);
@@ -0,0 +1,489 @@
+// Audit trail for changes made through the library.
+// Every insert, update, delete and load writes one row per affected record into
+// the audit table, with JSON images of the record before and after the change.
+use crate::{data_columns, has_column, with_savepoint, DELETED_AT_COLUMN};
+use once_cell::sync::Lazy;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+pub const AUDIT_TABLE: &str = "audit_log";
+
+// Hands out operation ids, so connections writing at the same time never share one
+pub const AUDIT_OPERATIONS_TABLE: &str = "audit_operations";
+
+// User label recorded with each change, set once by the CLI
+static AUDIT_USER: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditOperation {
+    Insert,
+    Update,
+    Delete,
+    Load,
+    Undo,
+    SoftDelete,
+    Restore,
+    Purge,
+    Drop,
+}
+
+impl AuditOperation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditOperation::Insert => "INSERT",
+            AuditOperation::Update => "UPDATE",
+            AuditOperation::Delete => "DELETE",
+            AuditOperation::Load => "LOAD",
+            AuditOperation::Undo => "UNDO",
+            AuditOperation::SoftDelete => "SOFT_DELETE",
+            AuditOperation::Restore => "RESTORE",
+            AuditOperation::Purge => "PURGE",
+            AuditOperation::Drop => "DROP",
+        }
+    }
+}
+
+impl fmt::Display for AuditOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AuditOperation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "INSERT" => Ok(AuditOperation::Insert),
+            "UPDATE" => Ok(AuditOperation::Update),
+            "DELETE" => Ok(AuditOperation::Delete),
+            "LOAD" => Ok(AuditOperation::Load),
+            "UNDO" => Ok(AuditOperation::Undo),
+            "SOFT_DELETE" => Ok(AuditOperation::SoftDelete),
+            "RESTORE" => Ok(AuditOperation::Restore),
+            "PURGE" => Ok(AuditOperation::Purge),
+            "DROP" => Ok(AuditOperation::Drop),
+            other => Err(format!("unknown audit operation '{}'", other)),
+        }
+    }
+}
+
+// One audited change to one record
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    pub entry_id: i64,
+    pub op_id: i64,
+    pub operation: AuditOperation,
+    pub table_name: String,
+    pub record_id: i64,
+    pub before_values: Option<String>,
+    pub after_values: Option<String>,
+    pub changed_at: String,
+    pub user_label: String,
+    pub undone_by_op: Option<i64>,
+}
+
+// Set the user label written with subsequent changes
+pub fn set_audit_user(user: &str) {
+    *AUDIT_USER.lock().unwrap() = Some(user.to_string());
+}
+
+// The label set by set_audit_user, falling back to the login name
+pub fn audit_user() -> String {
+    if let Some(user) = AUDIT_USER.lock().unwrap().as_ref() {
+        return user.clone();
+    }
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+// Create the audit table
+pub fn create_audit_table(conn: &Connection) -> Result<(), Box<dyn Error>> {
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {table} (
+            entry_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            op_id INTEGER NOT NULL,
+            operation TEXT NOT NULL,
+            table_name TEXT NOT NULL,
+            record_id INTEGER NOT NULL,
+            before_values TEXT,
+            after_values TEXT,
+            changed_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+            user_label TEXT NOT NULL,
+            undone_by_op INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS {table}_record ON {table} (table_name, record_id);
+        CREATE INDEX IF NOT EXISTS {table}_op ON {table} (op_id);
+        CREATE TABLE IF NOT EXISTS {operations} (
+            op_id INTEGER PRIMARY KEY AUTOINCREMENT
+        );",
+        table = AUDIT_TABLE,
+        operations = AUDIT_OPERATIONS_TABLE
+    ))?;
+    Ok(())
+}
+
+// Start a new audited operation and return its id; all entries
+// written for one insert, update, delete or load share this id.
+// The id comes from an insert, which holds the write lock until the
+// operation's transaction ends, so no other connection can be given it.
+pub(crate) fn begin_operation(conn: &Connection) -> Result<i64, Box<dyn Error>> {
+    create_audit_table(conn)?;
+    conn.execute(
+        &format!("INSERT INTO {} DEFAULT VALUES", AUDIT_OPERATIONS_TABLE),
+        [],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+// Columns captured in a record image; deleted_at is included once a table uses soft deletes
+fn image_columns(conn: &Connection, table_name: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut columns = data_columns(conn, table_name)?;
+    if has_column(conn, table_name, DELETED_AT_COLUMN)? {
+        columns.push(DELETED_AT_COLUMN.to_string());
+    }
+    Ok(columns)
+}
+
+// JSON image of a record, or None if it does not exist.
+// deleted_at only appears while it is set, so images of live records
+// look the same before and after a table starts using soft deletes.
+pub(crate) fn record_image(
+    conn: &Connection,
+    table_name: &str,
+    id: i64,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let fields: Vec<String> = data_columns(conn, table_name)?
+        .iter()
+        .map(|column| format!("'{0}', {0}", column))
+        .collect();
+    let image = if has_column(conn, table_name, DELETED_AT_COLUMN)? {
+        format!(
+            "CASE WHEN {1} IS NULL THEN json_object('id', id, {0}) ELSE json_object('id', id, {0}, '{1}', {1}) END",
+            fields.join(", "),
+            DELETED_AT_COLUMN
+        )
+    } else {
+        format!("json_object('id', id, {})", fields.join(", "))
+    };
+    let image_query = format!("SELECT {} FROM {} WHERE id = ?", image, table_name);
+    let image = conn
+        .prepare_cached(&image_query)?
+        .query_row(params![id], |row| row.get(0))
+        .optional()?;
+    Ok(image)
+}
+
+// Write one audit entry
+pub(crate) fn write_entry(
+    conn: &Connection,
+    op_id: i64,
+    operation: AuditOperation,
+    table_name: &str,
+    record_id: i64,
+    before_values: Option<&str>,
+    after_values: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let insert_query = format!(
+        "INSERT INTO {} (op_id, operation, table_name, record_id, before_values, after_values, user_label) VALUES (?, ?, ?, ?, ?, ?, ?)",
+        AUDIT_TABLE
+    );
+    conn.prepare_cached(&insert_query)?.execute(params![
+        op_id,
+        operation.as_str(),
+        table_name,
+        record_id,
+        before_values,
+        after_values,
+        audit_user()
+    ])?;
+    Ok(())
+}
+
+// Audit entries matching `condition`, in the order they were written
+fn query_entries(
+    conn: &Connection,
+    condition: &str,
+    params: &[&dyn rusqlite::ToSql],
+) -> Result<Vec<AuditEntry>, Box<dyn Error>> {
+    create_audit_table(conn)?;
+    let entries_query = format!(
+        "SELECT entry_id, op_id, operation, table_name, record_id, before_values, after_values, changed_at, user_label, undone_by_op
+         FROM {} WHERE {} ORDER BY entry_id",
+        AUDIT_TABLE, condition
+    );
+    let mut stmt = conn.prepare(&entries_query)?;
+    let rows = stmt.query_map(params, |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, i64>(4)?,
+            row.get::<_, Option<String>>(5)?,
+            row.get::<_, Option<String>>(6)?,
+            row.get::<_, String>(7)?,
+            row.get::<_, String>(8)?,
+            row.get::<_, Option<i64>>(9)?,
+        ))
+    })?;
+
+    let mut entries = Vec::new();
+    for row in rows {
+        let (
+            entry_id,
+            op_id,
+            operation,
+            table_name,
+            record_id,
+            before_values,
+            after_values,
+            changed_at,
+            user_label,
+            undone_by_op,
+        ) = row?;
+        entries.push(AuditEntry {
+            entry_id,
+            op_id,
+            operation: operation.parse()?,
+            table_name,
+            record_id,
+            before_values,
+            after_values,
+            changed_at,
+            user_label,
+            undone_by_op,
+        });
+    }
+    Ok(entries)
+}
+
+// All audit entries for a record, oldest first
+pub fn record_history(
+    conn: &Connection,
+    table_name: &str,
+    id: i32,
+) -> Result<Vec<AuditEntry>, Box<dyn Error>> {
+    query_entries(
+        conn,
+        "table_name = ? AND record_id = ?",
+        params![table_name, id],
+    )
+}
+
+// All audit entries written by one operation
+pub fn operation_entries(conn: &Connection, op_id: i64) -> Result<Vec<AuditEntry>, Box<dyn Error>> {
+    query_entries(conn, "op_id = ?", params![op_id])
+}
+
+// Print the change history of a record
+pub fn history_exec(conn: &Connection, table_name: &str, id: i32) -> Result<(), Box<dyn Error>> {
+    let entries = record_history(conn, table_name, id)?;
+    if entries.is_empty() {
+        println!(
+            "No history for record with ID '{}' in table '{}'.",
+            id, table_name
+        );
+        return Ok(());
+    }
+
+    for entry in entries {
+        println!(
+            "[op {}] {} {} by {}",
+            entry.op_id, entry.changed_at, entry.operation, entry.user_label
+        );
+        if let Some(undone_by_op) = entry.undone_by_op {
+            println!("  undone by op {}", undone_by_op);
+        }
+        if let Some(before) = &entry.before_values {
+            println!("  before: {}", before);
+        }
+        if let Some(after) = &entry.after_values {
+            println!("  after:  {}", after);
+        }
+    }
+    Ok(())
+}
+
+// Record that a table was dropped. Its records are gone, so the open entries
+// for it are closed by the drop and undo skips them from now on.
+pub(crate) fn record_drop(conn: &Connection, table_name: &str) -> Result<(), Box<dyn Error>> {
+    let audited: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = ?",
+        params![AUDIT_TABLE],
+        |row| row.get(0),
+    )?;
+    // Nothing to close in a database that was never audited
+    if !audited || table_name == AUDIT_TABLE {
+        return Ok(());
+    }
+    let op_id = begin_operation(conn)?;
+    write_entry(conn, op_id, AuditOperation::Drop, table_name, 0, None, None)?;
+    conn.execute(
+        &format!(
+            "UPDATE {} SET undone_by_op = ? WHERE table_name = ? AND op_id != ? AND undone_by_op IS NULL",
+            AUDIT_TABLE
+        ),
+        params![op_id, table_name, op_id],
+    )?;
+    Ok(())
+}
+
+// Most recent operation that has not been undone, ignoring undos and drops
+// themselves and operations on tables that no longer exist
+fn last_undoable_operation(conn: &Connection) -> Result<Option<i64>, Box<dyn Error>> {
+    create_audit_table(conn)?;
+    let op_id = conn.query_row(
+        &format!(
+            "SELECT MAX(op_id) FROM {} WHERE operation NOT IN (?, ?) AND undone_by_op IS NULL
+             AND table_name IN (SELECT name FROM sqlite_master WHERE type = 'table')",
+            AUDIT_TABLE
+        ),
+        params![AuditOperation::Undo.as_str(), AuditOperation::Drop.as_str()],
+        |row| row.get(0),
+    )?;
+    Ok(op_id)
+}
+
+// Put one record back to its image before the audited change
+fn revert_entry(
+    conn: &Connection,
+    undo_op_id: i64,
+    entry: &AuditEntry,
+) -> Result<(), Box<dyn Error>> {
+    let table_name = entry.table_name.as_str();
+    let current = record_image(conn, table_name, entry.record_id)?;
+    if current != entry.after_values {
+        return Err(format!(
+            "record with ID '{}' in table '{}' has changed since op {}; undo the later operations first",
+            entry.record_id, table_name, entry.op_id
+        )
+        .into());
+    }
+
+    let columns = image_columns(conn, table_name)?;
+    match &entry.before_values {
+        // The change created the record, so remove it
+        None => {
+            conn.execute(
+                &format!("DELETE FROM {} WHERE id = ?", table_name),
+                params![entry.record_id],
+            )?;
+        }
+        // The change deleted the record, so insert it again with its old id
+        Some(before) if current.is_none() => {
+            let values: Vec<String> = columns
+                .iter()
+                .map(|column| format!("json_extract(?1, '$.{}')", column))
+                .collect();
+            conn.execute(
+                &format!(
+                    "INSERT INTO {} (id, {}) VALUES (?2, {})",
+                    table_name,
+                    columns.join(", "),
+                    values.join(", ")
+                ),
+                params![before, entry.record_id],
+            )?;
+        }
+        // The change updated the record, so restore the old values
+        Some(before) => {
+            let assignments: Vec<String> = columns
+                .iter()
+                .map(|column| format!("{0} = json_extract(?1, '$.{0}')", column))
+                .collect();
+            conn.execute(
+                &format!(
+                    "UPDATE {} SET {} WHERE id = ?2",
+                    table_name,
+                    assignments.join(", ")
+                ),
+                params![before, entry.record_id],
+            )?;
+        }
+    }
+
+    let after = record_image(conn, table_name, entry.record_id)?;
+    write_entry(
+        conn,
+        undo_op_id,
+        AuditOperation::Undo,
+        table_name,
+        entry.record_id,
+        current.as_deref(),
+        after.as_deref(),
+    )
+}
+
+// Revert an audited operation, by default the most recent one, in a single transaction
+pub fn undo_exec(conn: &Connection, op_id: Option<i64>) -> Result<(), Box<dyn Error>> {
+    let op_id = match op_id {
+        Some(op_id) => op_id,
+        None => match last_undoable_operation(conn)? {
+            Some(op_id) => op_id,
+            None => {
+                println!("Nothing to undo.");
+                return Ok(());
+            }
+        },
+    };
+
+    let entries = operation_entries(conn, op_id)?;
+    let first = match entries.first() {
+        Some(first) => first.clone(),
+        None => return Err(format!("no audited operation with op id {}", op_id).into()),
+    };
+    // Undoing an undo would redo the original change
+    if first.operation == AuditOperation::Undo {
+        return Err(format!("op {} is an undo and cannot be undone", op_id).into());
+    }
+    if first.operation == AuditOperation::Drop {
+        return Err(format!(
+            "op {} dropped table '{}' and cannot be undone",
+            op_id, first.table_name
+        )
+        .into());
+    }
+    if let Some(undone_by_op) = first.undone_by_op {
+        let closed_by_drop = operation_entries(conn, undone_by_op)?
+            .first()
+            .is_some_and(|entry| entry.operation == AuditOperation::Drop);
+        if closed_by_drop {
+            return Err(format!(
+                "op {} cannot be undone: table '{}' was dropped by op {}",
+                op_id, first.table_name, undone_by_op
+            )
+            .into());
+        }
+        return Err(format!("op {} was already undone by op {}", op_id, undone_by_op).into());
+    }
+
+    with_savepoint(conn, || {
+        let undo_op_id = begin_operation(conn)?;
+        // Revert newest first so records touched twice end up in their oldest state
+        for entry in entries.iter().rev() {
+            revert_entry(conn, undo_op_id, entry)?;
+        }
+        conn.execute(
+            &format!(
+                "UPDATE {} SET undone_by_op = ? WHERE op_id = ?",
+                AUDIT_TABLE
+            ),
+            params![undo_op_id, op_id],
+        )?;
+        Ok(())
+    })?;
+
+    println!(
+        "Undid op {} ({} of {} record(s) in table '{}').",
+        op_id,
+        first.operation,
+        entries.len(),
+        first.table_name
+    );
+    Ok(())
+}
@@ -0,0 +1,110 @@
+// Online backup and restore of the whole database file.
+// Built on SQLite's backup API, so a backup can be taken while other
+// connections keep reading and writing the database.
+use crate::connection::{
+    connection_settings, open_connection, open_connection_with_flags, DEFAULT_BUSY_TIMEOUT_MS,
+};
+use rusqlite::backup::{Backup, StepResult};
+use rusqlite::Connection;
+use std::error::Error;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Pages copied per backup step when no other value is given
+pub const DEFAULT_PAGES_PER_STEP: i32 = 64;
+
+// How long to wait before retrying a step that hit a lock
+const BUSY_RETRY_PAUSE: Duration = Duration::from_millis(25);
+
+// Copy `from` into `to` a few pages at a time, printing progress after each step.
+// A step that hits a lock is retried for up to the busy timeout.
+fn copy_pages(
+    from: &Connection,
+    to: &mut Connection,
+    pages_per_step: i32,
+) -> Result<(), Box<dyn Error>> {
+    let busy_timeout = Duration::from_millis(
+        connection_settings()
+            .busy_timeout
+            .unwrap_or(DEFAULT_BUSY_TIMEOUT_MS),
+    );
+    let backup = Backup::new(from, to)?;
+    let mut blocked_since = None;
+    loop {
+        let result = backup.step(pages_per_step)?;
+        if !matches!(result, StepResult::Busy | StepResult::Locked) {
+            blocked_since = None;
+        }
+        match result {
+            StepResult::Done => break,
+            StepResult::More => {
+                let progress = backup.progress();
+                println!(
+                    "Copied {}/{} pages",
+                    progress.pagecount - progress.remaining,
+                    progress.pagecount
+                );
+            }
+            // Another connection holds a lock; wait and retry the same step
+            _ => {
+                let since = *blocked_since.get_or_insert_with(Instant::now);
+                if since.elapsed() >= busy_timeout {
+                    return Err(format!(
+                        "database is locked by another connection; gave up after {} ms",
+                        busy_timeout.as_millis()
+                    )
+                    .into());
+                }
+                thread::sleep(BUSY_RETRY_PAUSE);
+            }
+        }
+    }
+    let progress = backup.progress();
+    println!(
+        "Copied {}/{} pages",
+        progress.pagecount - progress.remaining,
+        progress.pagecount
+    );
+    Ok(())
+}
+
+// Run PRAGMA integrity_check and fail unless it reports "ok"
+pub fn check_integrity(conn: &Connection) -> Result<(), Box<dyn Error>> {
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    let problems = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    if problems.len() == 1 && problems[0] == "ok" {
+        Ok(())
+    } else {
+        Err(format!("integrity check failed: {}", problems.join("; ")).into())
+    }
+}
+
+// Back up the open database to a file
+pub fn backup_exec(
+    conn: &Connection,
+    dest_path: &str,
+    pages_per_step: i32,
+) -> Result<(), Box<dyn Error>> {
+    let mut dest = open_connection(dest_path)?;
+    copy_pages(conn, &mut dest, pages_per_step)?;
+    check_integrity(&dest)?;
+    println!("Database backed up to '{}' and verified.", dest_path);
+    Ok(())
+}
+
+// Replace the open database with the contents of a backup file
+pub fn restore_backup_exec(
+    conn: &mut Connection,
+    src_path: &str,
+    pages_per_step: i32,
+) -> Result<(), Box<dyn Error>> {
+    let src = open_connection_with_flags(src_path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    // Refuse to overwrite the database with a damaged backup
+    check_integrity(&src)?;
+    copy_pages(&src, conn, pages_per_step)?;
+    check_integrity(conn)?;
+    println!("Database restored from '{}' and verified.", src_path);
+    Ok(())
+}
@@ -0,0 +1,351 @@
+// Cross-check the Rust loader against the Python `mylib` pipeline: load the same
+// CSV on both sides, then diff the row sets and per-column aggregates.
+use crate::connection::open_in_memory;
+use crate::{create_table, data_columns, load_data_from_csv, profile};
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+// Table the Python transform_load.load() writes into FifaDB.db
+pub const PYTHON_TABLE: &str = "FifaDB";
+pub const PYTHON_DB_FILE: &str = "FifaDB.db";
+
+// Differing rows shown per side
+const SAMPLE_ROWS: usize = 5;
+
+// Digits compared for REAL values, so both parsers' last-bit rounding agrees
+const REAL_PRECISION: i32 = 9;
+
+// One row's data columns
+pub type Row = Vec<Value>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregateDiff {
+    // e.g. "SUM(population_share)"
+    pub name: String,
+    pub rust: Value,
+    pub python: Value,
+}
+
+impl AggregateDiff {
+    pub fn matches(&self) -> bool {
+        match (&self.rust, &self.python) {
+            (Value::Real(a), Value::Real(b)) => {
+                (a - b).abs() <= 1e-9 * a.abs().max(b.abs()).max(1.0)
+            }
+            (a, b) => a == b,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Comparison {
+    pub rust_rows: i64,
+    pub python_rows: i64,
+    pub only_in_rust: i64,
+    pub only_in_python: i64,
+    // Up to a few rows from each side with no match on the other
+    pub rust_samples: Vec<Row>,
+    pub python_samples: Vec<Row>,
+    pub aggregates: Vec<AggregateDiff>,
+}
+
+impl Comparison {
+    pub fn is_match(&self) -> bool {
+        self.rust_rows == self.python_rows
+            && self.only_in_rust == 0
+            && self.only_in_python == 0
+            && self.aggregates.iter().all(AggregateDiff::matches)
+    }
+}
+
+// Select list that rounds REAL values so equal data compares equal
+fn comparable_columns(columns: &[String]) -> String {
+    columns
+        .iter()
+        .map(|c| {
+            format!(
+                "CASE WHEN typeof({0}) = 'real' THEN round({0}, {1}) ELSE {0} END AS {0}",
+                c, REAL_PRECISION
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Rows of `left` with no equal row in `right`: the count and a few examples
+fn rows_missing_from(
+    conn: &Connection,
+    left: &str,
+    right: &str,
+    columns: &[String],
+) -> Result<(i64, Vec<Row>), Box<dyn Error>> {
+    let select = comparable_columns(columns);
+    let difference = format!(
+        "SELECT {0} FROM {1} EXCEPT SELECT {0} FROM {2}",
+        select, left, right
+    );
+    let count = conn.query_row(
+        &format!("SELECT COUNT(*) FROM ({})", difference),
+        [],
+        |row| row.get(0),
+    )?;
+    let mut stmt = conn.prepare(&format!("{} LIMIT {}", difference, SAMPLE_ROWS))?;
+    let samples = stmt
+        .query_map([], |row| {
+            (0..columns.len())
+                .map(|i| row.get::<_, Value>(i))
+                .collect::<Result<Vec<_>, _>>()
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((count, samples))
+}
+
+// Row count, then COUNT/SUM/MIN/MAX of numeric columns and distinct counts of text
+fn aggregate_expressions(
+    conn: &Connection,
+    table: &str,
+    columns: &[String],
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut expressions = vec!["COUNT(*)".to_string()];
+    for column in columns {
+        let numeric: bool = conn.query_row(
+            &format!(
+                "SELECT COUNT(*) > 0 AND COUNT(*) = SUM(typeof({0}) IN ('integer', 'real'))
+                 FROM {1} WHERE {0} IS NOT NULL",
+                column, table
+            ),
+            [],
+            |row| row.get(0),
+        )?;
+        if numeric {
+            for function in ["COUNT", "SUM", "MIN", "MAX"] {
+                expressions.push(format!("{}({})", function, column));
+            }
+        } else {
+            expressions.push(format!("COUNT({})", column));
+            expressions.push(format!("COUNT(DISTINCT {})", column));
+        }
+    }
+    Ok(expressions)
+}
+
+fn aggregate_values(
+    conn: &Connection,
+    table: &str,
+    expressions: &[String],
+) -> Result<Vec<Value>, Box<dyn Error>> {
+    let values = conn.query_row(
+        &format!("SELECT {} FROM {}", expressions.join(", "), table),
+        [],
+        |row| {
+            (0..expressions.len())
+                .map(|i| row.get::<_, Value>(i))
+                .collect::<Result<Vec<_>, _>>()
+        },
+    )?;
+    Ok(values)
+}
+
+// Compare a table on `conn` with the Python table in the database at `python_db`.
+// The Python database is attached read-only, so it is never modified.
+pub fn compare_tables(
+    conn: &Connection,
+    rust_table: &str,
+    python_db: &str,
+    python_table: &str,
+) -> Result<Comparison, Box<dyn Error>> {
+    if !Path::new(python_db).exists() {
+        return Err(format!("Python database '{}' not found", python_db).into());
+    }
+    conn.execute(
+        "ATTACH DATABASE ? AS python",
+        [format!("file:{}?mode=ro", python_db)],
+    )?;
+    let result = (|| {
+        let columns = data_columns(conn, rust_table)?;
+        let python = format!("python.{}", python_table);
+        let (only_in_rust, rust_samples) = rows_missing_from(conn, rust_table, &python, &columns)?;
+        let (only_in_python, python_samples) =
+            rows_missing_from(conn, &python, rust_table, &columns)?;
+
+        // The Python table's column types decide which aggregates both sides compute
+        let expressions = aggregate_expressions(conn, &python, &columns)?;
+        let rust_values = aggregate_values(conn, rust_table, &expressions)?;
+        let python_values = aggregate_values(conn, &python, &expressions)?;
+        let count = |values: &[Value]| match values.first() {
+            Some(Value::Integer(n)) => *n,
+            _ => 0,
+        };
+        let comparison = Comparison {
+            rust_rows: count(&rust_values),
+            python_rows: count(&python_values),
+            only_in_rust,
+            only_in_python,
+            rust_samples,
+            python_samples,
+            aggregates: expressions
+                .into_iter()
+                .zip(rust_values.into_iter().zip(python_values))
+                .map(|(name, (rust, python))| AggregateDiff { name, rust, python })
+                .collect(),
+        };
+        profile::add_rows((comparison.rust_rows + comparison.python_rows) as usize);
+        Ok(comparison)
+    })();
+    conn.execute("DETACH DATABASE python", [])?;
+    result
+}
+
+// Load a CSV into a table with the Rust library and return how long it took
+pub fn time_rust_load(
+    conn: &Connection,
+    table: &str,
+    csv_path: &str,
+) -> Result<Duration, Box<dyn Error>> {
+    let start = Instant::now();
+    create_table(conn, table)?;
+    load_data_from_csv(conn, table, csv_path)?;
+    Ok(start.elapsed())
+}
+
+// Run the Python loader in `python_dir`, where it writes FifaDB.db, and return
+// how long the subprocess took, interpreter start-up included
+pub fn time_python_load(
+    python: &str,
+    python_dir: &str,
+    csv_path: &str,
+) -> Result<Duration, Box<dyn Error>> {
+    let csv_path = fs::canonicalize(csv_path)?;
+    let script = "import sys\nfrom mylib.transform_load import load\nload(sys.argv[1])";
+    let start = Instant::now();
+    let output = Command::new(python)
+        .args(["-c", script])
+        .arg(&csv_path)
+        .current_dir(python_dir)
+        .output()
+        .map_err(|e| format!("could not run '{}': {}", python, e))?;
+    let elapsed = start.elapsed();
+    if !output.status.success() {
+        return Err(format!(
+            "Python load failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(elapsed)
+}
+
+fn format_row(row: &[Value]) -> String {
+    row.iter()
+        .map(|value| match value {
+            Value::Null => "NULL".to_string(),
+            Value::Integer(i) => i.to_string(),
+            Value::Real(f) => f.to_string(),
+            Value::Text(s) => format!("'{}'", s),
+            Value::Blob(b) => format!("<{} bytes>", b.len()),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Print the differences found, or that there were none
+pub fn print_comparison(comparison: &Comparison) {
+    println!(
+        "Rows: Rust {}, Python {}",
+        comparison.rust_rows, comparison.python_rows
+    );
+    for (side, count, samples) in [
+        ("Rust", comparison.only_in_rust, &comparison.rust_samples),
+        (
+            "Python",
+            comparison.only_in_python,
+            &comparison.python_samples,
+        ),
+    ] {
+        if count > 0 {
+            println!("{} rows only in the {} table:", count, side);
+            for row in samples {
+                println!("  ({})", format_row(row));
+            }
+        }
+    }
+    for aggregate in comparison.aggregates.iter().filter(|a| !a.matches()) {
+        println!(
+            "{} differs: Rust {}, Python {}",
+            aggregate.name,
+            format_row(std::slice::from_ref(&aggregate.rust)),
+            format_row(std::slice::from_ref(&aggregate.python))
+        );
+    }
+    if comparison.is_match() {
+        println!(
+            "Tables match: same rows and {} matching aggregates.",
+            comparison.aggregates.len()
+        );
+    }
+}
+
+// How to produce the Python side of a comparison
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PythonSide {
+    // Interpreter to run; None compares against the existing database
+    pub interpreter: Option<String>,
+    // Directory holding the mylib package, where the loader writes FifaDB.db
+    pub project_dir: String,
+    // Database to compare against; defaults to FifaDB.db in project_dir
+    pub database: Option<String>,
+}
+
+impl PythonSide {
+    pub fn database_path(&self) -> String {
+        self.database.clone().unwrap_or_else(|| {
+            Path::new(&self.project_dir)
+                .join(PYTHON_DB_FILE)
+                .to_string_lossy()
+                .into_owned()
+        })
+    }
+}
+
+// Load the CSV with both implementations, time them and report any difference.
+// The Rust side loads into an in-memory database, leaving the CLI's untouched.
+pub fn compare_exec(csv_path: &str, python: &PythonSide) -> Result<(), Box<dyn Error>> {
+    let conn = open_in_memory()?;
+    let rust_table = "rust_load";
+    let rust_time = time_rust_load(&conn, rust_table, csv_path)?;
+    let python_time = match &python.interpreter {
+        Some(interpreter) => Some(time_python_load(
+            interpreter,
+            &python.project_dir,
+            csv_path,
+        )?),
+        None => None,
+    };
+
+    let python_db = python.database_path();
+    let comparison = compare_tables(&conn, rust_table, &python_db, PYTHON_TABLE)?;
+
+    println!("\n{:<8} {:>12}", "Loader", "Load time");
+    println!("{:<8} {:>10.3} s", "Rust", rust_time.as_secs_f64());
+    match python_time {
+        Some(time) => println!("{:<8} {:>10.3} s", "Python", time.as_secs_f64()),
+        None => println!("{:<8} {:>12}", "Python", "not run"),
+    }
+    println!();
+    print_comparison(&comparison);
+    if comparison.is_match() {
+        Ok(())
+    } else {
+        Err(format!(
+            "'{}' differs from the Rust load of '{}'",
+            python_db, csv_path
+        )
+        .into())
+    }
+}
@@ -0,0 +1,47 @@
+// Transparent decompression of gzip and zip input, detected by magic bytes
+// rather than file extension so piped and renamed files work too.
+use flate2::read::MultiGzDecoder;
+use std::error::Error;
+use std::io::{BufReader, Cursor, Read};
+use zip::ZipArchive;
+
+const GZIP_MAGIC: &[u8] = b"\x1F\x8B";
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+// Read the first file stored in a zip archive, skipping directory entries
+fn first_zip_entry(reader: impl Read) -> Result<Vec<u8>, Box<dyn Error>> {
+    // Zip archives keep their index at the end, so they need a seekable buffer
+    let mut archive_bytes = Vec::new();
+    BufReader::new(reader).read_to_end(&mut archive_bytes)?;
+    let mut archive = ZipArchive::new(Cursor::new(archive_bytes))?;
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        if entry.is_file() {
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents)?;
+            return Ok(contents);
+        }
+    }
+    Err("zip archive contains no files".into())
+}
+
+// Wrap a reader so that gzip or zip content comes out decompressed;
+// anything else is passed through unchanged
+pub fn decompressed<'a>(reader: impl Read + 'a) -> Result<Box<dyn Read + 'a>, Box<dyn Error>> {
+    // A pipe may hand over fewer bytes than the magic per read, so keep reading
+    // until there are enough or the input ends, then put them back in front
+    let mut reader = reader;
+    let mut magic = Vec::with_capacity(ZIP_MAGIC.len());
+    (&mut reader)
+        .take(ZIP_MAGIC.len() as u64)
+        .read_to_end(&mut magic)?;
+    let reader = Cursor::new(magic.clone()).chain(reader);
+    if magic.starts_with(GZIP_MAGIC) {
+        Ok(Box::new(MultiGzDecoder::new(reader)))
+    } else if magic.starts_with(ZIP_MAGIC) {
+        Ok(Box::new(Cursor::new(first_zip_entry(reader)?)))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
@@ -0,0 +1,275 @@
+// Defaults read from config files: a user-level file, then a project-local
+// .sqlite.toml, each overriding the one before and overridden by command-line
+// flags. Every value remembers where it came from so `config show` can say.
+use crate::connection::{ConnectionSettings, JournalMode, Synchronous, DEFAULT_BUSY_TIMEOUT_MS};
+use crate::sql::OutputFormat;
+use crate::FIFA_URL;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const PROJECT_CONFIG_FILE: &str = ".sqlite.toml";
+pub const DEFAULT_DATABASE: &str = "my_database.db";
+
+// Where a value was set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    Default,
+    File(PathBuf),
+    Flag,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::File(path) => write!(f, "{}", path.display()),
+            Origin::Flag => write!(f, "command line"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setting<T> {
+    pub value: T,
+    pub origin: Origin,
+}
+
+impl<T> Setting<T> {
+    fn default(value: T) -> Setting<T> {
+        Setting {
+            value,
+            origin: Origin::Default,
+        }
+    }
+
+    // Replace the value when one is given
+    pub fn set(&mut self, value: Option<T>, origin: &Origin) {
+        if let Some(value) = value {
+            self.value = value;
+            self.origin = origin.clone();
+        }
+    }
+}
+
+// The layout of a config file; every key is optional
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    pub database: Option<String>,
+    // Table used by commands that take one when none is named
+    pub table: Option<String>,
+    pub source_url: Option<String>,
+    // Output format of the sql command
+    pub output_format: Option<String>,
+    pub connection: ConnectionSettings,
+}
+
+// Read and check a config file
+pub fn read_config_file(path: &Path) -> Result<ConfigFile, Box<dyn Error>> {
+    let text = fs::read_to_string(path)?;
+    let file: ConfigFile =
+        toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if let Some(format) = &file.output_format {
+        format
+            .parse::<OutputFormat>()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    Ok(file)
+}
+
+// Effective settings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub database: Setting<String>,
+    pub table: Setting<Option<String>>,
+    pub source_url: Setting<String>,
+    pub output_format: Setting<OutputFormat>,
+    pub journal_mode: Setting<Option<JournalMode>>,
+    pub synchronous: Setting<Option<Synchronous>>,
+    pub cache_size: Setting<Option<i64>>,
+    pub mmap_size: Setting<Option<i64>>,
+    pub busy_timeout: Setting<u64>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            database: Setting::default(DEFAULT_DATABASE.to_string()),
+            table: Setting::default(None),
+            source_url: Setting::default(FIFA_URL.to_string()),
+            output_format: Setting::default(OutputFormat::default()),
+            journal_mode: Setting::default(None),
+            synchronous: Setting::default(None),
+            cache_size: Setting::default(None),
+            mmap_size: Setting::default(None),
+            busy_timeout: Setting::default(DEFAULT_BUSY_TIMEOUT_MS),
+        }
+    }
+}
+
+impl Config {
+    // Defaults, then each existing file in `paths` in turn
+    pub fn load(paths: &[PathBuf]) -> Result<Config, Box<dyn Error>> {
+        let mut config = Config::default();
+        for path in paths.iter().filter(|path| path.is_file()) {
+            let file = read_config_file(path)?;
+            config.apply_file(&file, &Origin::File(path.clone()));
+        }
+        Ok(config)
+    }
+
+    pub fn apply_file(&mut self, file: &ConfigFile, origin: &Origin) {
+        self.database.set(file.database.clone(), origin);
+        self.table.set(file.table.clone().map(Some), origin);
+        self.source_url.set(file.source_url.clone(), origin);
+        // read_config_file has checked the format
+        self.output_format.set(
+            file.output_format.as_deref().and_then(|f| f.parse().ok()),
+            origin,
+        );
+        self.apply_connection(&file.connection, origin);
+    }
+
+    pub fn apply_connection(&mut self, settings: &ConnectionSettings, origin: &Origin) {
+        self.journal_mode
+            .set(settings.journal_mode.map(Some), origin);
+        self.synchronous.set(settings.synchronous.map(Some), origin);
+        self.cache_size.set(settings.cache_size.map(Some), origin);
+        self.mmap_size.set(settings.mmap_size.map(Some), origin);
+        self.busy_timeout.set(settings.busy_timeout, origin);
+    }
+
+    pub fn connection_settings(&self) -> ConnectionSettings {
+        ConnectionSettings {
+            journal_mode: self.journal_mode.value,
+            synchronous: self.synchronous.value,
+            cache_size: self.cache_size.value,
+            mmap_size: self.mmap_size.value,
+            busy_timeout: Some(self.busy_timeout.value),
+        }
+    }
+
+    // Key, value and origin of every setting, as spelled in config files
+    pub fn entries(&self) -> Vec<(&'static str, String, &Origin)> {
+        fn optional<T: ToString>(value: &Option<T>) -> String {
+            value
+                .as_ref()
+                .map_or_else(|| "(not set)".to_string(), T::to_string)
+        }
+        vec![
+            (
+                "database",
+                self.database.value.clone(),
+                &self.database.origin,
+            ),
+            ("table", optional(&self.table.value), &self.table.origin),
+            (
+                "source_url",
+                self.source_url.value.clone(),
+                &self.source_url.origin,
+            ),
+            (
+                "output_format",
+                self.output_format.value.to_string(),
+                &self.output_format.origin,
+            ),
+            (
+                "connection.journal_mode",
+                optional(&self.journal_mode.value),
+                &self.journal_mode.origin,
+            ),
+            (
+                "connection.synchronous",
+                optional(&self.synchronous.value),
+                &self.synchronous.origin,
+            ),
+            (
+                "connection.cache_size",
+                optional(&self.cache_size.value),
+                &self.cache_size.origin,
+            ),
+            (
+                "connection.mmap_size",
+                optional(&self.mmap_size.value),
+                &self.mmap_size.origin,
+            ),
+            (
+                "connection.busy_timeout",
+                self.busy_timeout.value.to_string(),
+                &self.busy_timeout.origin,
+            ),
+        ]
+    }
+}
+
+// $XDG_CONFIG_HOME/sqlite/config.toml, else ~/.config/sqlite/config.toml
+pub fn user_config_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("sqlite").join("config.toml"))
+}
+
+// Config files in the order they are applied: user-level, then project-local
+pub fn config_paths() -> Vec<PathBuf> {
+    user_config_path()
+        .into_iter()
+        .chain([PathBuf::from(PROJECT_CONFIG_FILE)])
+        .collect()
+}
+
+pub fn load_config() -> Result<Config, Box<dyn Error>> {
+    Config::load(&config_paths())
+}
+
+static CONFIG: Lazy<Mutex<Config>> = Lazy::new(|| Mutex::new(Config::default()));
+
+// Make `config` the effective configuration of this process
+pub fn set_config(config: Config) {
+    *CONFIG.lock().unwrap() = config;
+}
+
+pub fn config() -> Config {
+    CONFIG.lock().unwrap().clone()
+}
+
+// Print the effective settings and where each came from
+pub fn config_show_exec(config: &Config) {
+    let entries = config.entries();
+    let key_width = entries
+        .iter()
+        .map(|(key, _, _)| key.len())
+        .max()
+        .unwrap_or(0);
+    let value_width = entries
+        .iter()
+        .map(|(_, value, _)| value.len())
+        .max()
+        .unwrap_or(0);
+    for (key, value, origin) in entries {
+        println!(
+            "{:<key_width$}  {:<value_width$}  ({})",
+            key,
+            value,
+            origin,
+            key_width = key_width,
+            value_width = value_width
+        );
+    }
+    let files: Vec<String> = config_paths()
+        .iter()
+        .map(|path| {
+            let state = if path.is_file() { "" } else { " (not found)" };
+            format!("{}{}", path.display(), state)
+        })
+        .collect();
+    println!(
+        "\nConfig files, later ones taking precedence: {}",
+        files.join(", ")
+    );
+}
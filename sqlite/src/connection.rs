@@ -0,0 +1,198 @@
+// Connection tuning: journal mode, synchronous level, cache size, mmap size and
+// busy timeout. The settings are process-wide and applied to every connection
+// opened through open_connection, including the ones the library opens itself.
+use once_cell::sync::Lazy;
+use rusqlite::{Connection, DatabaseName, OpenFlags};
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// How long to wait for another connection's lock before failing with SQLITE_BUSY
+pub const DEFAULT_BUSY_TIMEOUT_MS: u64 = 5000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalMode {
+    Delete,
+    Truncate,
+    Persist,
+    Memory,
+    Wal,
+    Off,
+}
+
+impl FromStr for JournalMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "delete" => Ok(JournalMode::Delete),
+            "truncate" => Ok(JournalMode::Truncate),
+            "persist" => Ok(JournalMode::Persist),
+            "memory" => Ok(JournalMode::Memory),
+            "wal" => Ok(JournalMode::Wal),
+            "off" => Ok(JournalMode::Off),
+            other => Err(format!(
+                "unknown journal mode '{}' (expected delete, truncate, persist, memory, wal or off)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for JournalMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            JournalMode::Delete => "delete",
+            JournalMode::Truncate => "truncate",
+            JournalMode::Persist => "persist",
+            JournalMode::Memory => "memory",
+            JournalMode::Wal => "wal",
+            JournalMode::Off => "off",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Synchronous {
+    Off,
+    Normal,
+    Full,
+    Extra,
+}
+
+impl FromStr for Synchronous {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(Synchronous::Off),
+            "normal" => Ok(Synchronous::Normal),
+            "full" => Ok(Synchronous::Full),
+            "extra" => Ok(Synchronous::Extra),
+            other => Err(format!(
+                "unknown synchronous level '{}' (expected off, normal, full or extra)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Synchronous {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Synchronous::Off => "off",
+            Synchronous::Normal => "normal",
+            Synchronous::Full => "full",
+            Synchronous::Extra => "extra",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Unset values keep SQLite's defaults, except the busy timeout
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConnectionSettings {
+    pub journal_mode: Option<JournalMode>,
+    pub synchronous: Option<Synchronous>,
+    // Pages when positive, KiB when negative, as for PRAGMA cache_size
+    pub cache_size: Option<i64>,
+    // Bytes of the database file to memory-map; 0 turns memory mapping off
+    pub mmap_size: Option<i64>,
+    // Milliseconds; DEFAULT_BUSY_TIMEOUT_MS when not given
+    pub busy_timeout: Option<u64>,
+}
+
+impl ConnectionSettings {
+    // Settings from `overrides` where given, else these
+    pub fn merge(&self, overrides: &ConnectionSettings) -> ConnectionSettings {
+        ConnectionSettings {
+            journal_mode: overrides.journal_mode.or(self.journal_mode),
+            synchronous: overrides.synchronous.or(self.synchronous),
+            cache_size: overrides.cache_size.or(self.cache_size),
+            mmap_size: overrides.mmap_size.or(self.mmap_size),
+            busy_timeout: overrides.busy_timeout.or(self.busy_timeout),
+        }
+    }
+
+    // Set the pragmas on an open connection. The journal mode is a property of
+    // the database file, so read-only connections leave it alone.
+    pub fn apply(&self, conn: &Connection) -> Result<(), Box<dyn Error>> {
+        conn.busy_timeout(Duration::from_millis(
+            self.busy_timeout.unwrap_or(DEFAULT_BUSY_TIMEOUT_MS),
+        ))?;
+        if let Some(mode) = self.journal_mode {
+            if !conn.is_readonly(DatabaseName::Main)? {
+                let actual: String =
+                    conn.pragma_update_and_check(None, "journal_mode", mode.to_string(), |row| {
+                        row.get(0)
+                    })?;
+                // In-memory databases only support memory and off
+                if actual != mode.to_string() && conn.path().is_some_and(|p| !p.is_empty()) {
+                    return Err(format!(
+                        "journal mode {} was not applied (database uses {})",
+                        mode, actual
+                    )
+                    .into());
+                }
+            }
+        }
+        if let Some(level) = self.synchronous {
+            conn.pragma_update(None, "synchronous", level.to_string())?;
+        }
+        if let Some(size) = self.cache_size {
+            conn.pragma_update(None, "cache_size", size)?;
+        }
+        if let Some(size) = self.mmap_size {
+            // Returns the new size as a row
+            conn.pragma_update_and_check(None, "mmap_size", size, |_| Ok(()))?;
+        }
+        Ok(())
+    }
+}
+
+// Read settings from a TOML file of pragma names and values
+pub fn read_connection_settings(path: &str) -> Result<ConnectionSettings, Box<dyn Error>> {
+    let text = fs::read_to_string(path)?;
+    Ok(toml::from_str(&text)?)
+}
+
+static CONNECTION_SETTINGS: Lazy<Mutex<ConnectionSettings>> =
+    Lazy::new(|| Mutex::new(ConnectionSettings::default()));
+
+// Use these settings for every connection opened from now on
+pub fn set_connection_settings(settings: ConnectionSettings) {
+    *CONNECTION_SETTINGS.lock().unwrap() = settings;
+}
+
+pub fn connection_settings() -> ConnectionSettings {
+    CONNECTION_SETTINGS.lock().unwrap().clone()
+}
+
+// Open a database with the current connection settings applied
+pub fn open_connection<P: AsRef<Path>>(path: P) -> Result<Connection, Box<dyn Error>> {
+    open_connection_with_flags(path, OpenFlags::default())
+}
+
+pub fn open_connection_with_flags<P: AsRef<Path>>(
+    path: P,
+    flags: OpenFlags,
+) -> Result<Connection, Box<dyn Error>> {
+    let conn = Connection::open_with_flags(path, flags)?;
+    connection_settings().apply(&conn)?;
+    Ok(conn)
+}
+
+pub fn open_in_memory() -> Result<Connection, Box<dyn Error>> {
+    let conn = Connection::open_in_memory()?;
+    connection_settings().apply(&conn)?;
+    Ok(conn)
+}
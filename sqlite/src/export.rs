@@ -0,0 +1,175 @@
+// Export a table to CSV, JSON, NDJSON or a SQL INSERT dump.
+// Rows are written as they are read, so memory use does not grow with the table.
+use crate::{data_columns, live_rows_condition, profile};
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Ndjson,
+    Sql,
+}
+
+impl ExportFormat {
+    // Guess the format from a file extension
+    pub fn from_path(path: &str) -> Option<ExportFormat> {
+        let extension = Path::new(path).extension()?.to_str()?;
+        extension.to_lowercase().parse().ok()
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            "ndjson" | "jsonl" => Ok(ExportFormat::Ndjson),
+            "sql" => Ok(ExportFormat::Sql),
+            other => Err(format!(
+                "unknown export format '{}' (expected csv, json, ndjson or sql)",
+                other
+            )),
+        }
+    }
+}
+
+// Render a value as a CSV field; NULL becomes an empty field, which the loader
+// reads back as NULL
+fn csv_field(value: Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Integer(i) => i.to_string(),
+        Value::Real(f) => f.to_string(),
+        Value::Text(s) => s,
+        Value::Blob(b) => String::from_utf8_lossy(&b).into_owned(),
+    }
+}
+
+// Write the live records of a table to `out` and return how many were written.
+// All data columns are exported, so for the country table the CSV output has
+// the same header and column order that load_data_from_csv reads.
+pub fn export_table(
+    conn: &Connection,
+    table_name: &str,
+    format: ExportFormat,
+    out: &mut dyn Write,
+) -> Result<usize, Box<dyn Error>> {
+    let condition = live_rows_condition(conn, table_name)?;
+    let data_columns = data_columns(conn, table_name)?;
+    let columns = data_columns.join(", ");
+    let select_list = match format {
+        ExportFormat::Csv => columns.clone(),
+        ExportFormat::Json | ExportFormat::Ndjson => {
+            let fields: Vec<String> = data_columns
+                .iter()
+                .map(|column| format!("'{0}', {0}", column))
+                .collect();
+            format!("json_object({})", fields.join(", "))
+        }
+        ExportFormat::Sql => {
+            let values: Vec<String> = data_columns
+                .iter()
+                .map(|column| format!("quote({})", column))
+                .collect();
+            format!(
+                "'INSERT INTO {} ({}) VALUES (' || {} || ');'",
+                table_name,
+                columns,
+                values.join(" || ', ' || ")
+            )
+        }
+    };
+    let export_query = format!(
+        "SELECT {} FROM {} WHERE {} ORDER BY id",
+        select_list, table_name, condition
+    );
+    let mut stmt = conn.prepare(&export_query)?;
+    let mut rows = stmt.query([])?;
+    let mut count = 0;
+
+    match format {
+        ExportFormat::Csv => {
+            let mut wtr = csv::Writer::from_writer(&mut *out);
+            wtr.write_record(&data_columns)?;
+            while let Some(row) = rows.next()? {
+                let mut record = Vec::with_capacity(data_columns.len());
+                for i in 0..data_columns.len() {
+                    record.push(csv_field(row.get(i)?));
+                }
+                wtr.write_record(&record)?;
+                count += 1;
+            }
+            wtr.flush()?;
+        }
+        ExportFormat::Json => {
+            writeln!(out, "[")?;
+            while let Some(row) = rows.next()? {
+                let object: String = row.get(0)?;
+                if count > 0 {
+                    writeln!(out, ",")?;
+                }
+                write!(out, "  {}", object)?;
+                count += 1;
+            }
+            if count > 0 {
+                writeln!(out)?;
+            }
+            writeln!(out, "]")?;
+        }
+        ExportFormat::Ndjson => {
+            while let Some(row) = rows.next()? {
+                let object: String = row.get(0)?;
+                writeln!(out, "{}", object)?;
+                count += 1;
+            }
+        }
+        ExportFormat::Sql => {
+            writeln!(out, "BEGIN TRANSACTION;")?;
+            while let Some(row) = rows.next()? {
+                let statement: String = row.get(0)?;
+                writeln!(out, "{}", statement)?;
+                count += 1;
+            }
+            writeln!(out, "COMMIT;")?;
+        }
+    }
+    out.flush()?;
+    profile::add_rows(count);
+    Ok(count)
+}
+
+// Export a table to a file, or to stdout when no path is given
+pub fn export_exec(
+    conn: &Connection,
+    table_name: &str,
+    format: ExportFormat,
+    output_path: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    match output_path {
+        Some(path) => {
+            let mut out = BufWriter::new(File::create(path)?);
+            let count = export_table(conn, table_name, format, &mut out)?;
+            println!(
+                "Exported {} records from table '{}' to '{}'.",
+                count, table_name, path
+            );
+        }
+        None => {
+            let stdout = io::stdout();
+            let mut out = BufWriter::new(stdout.lock());
+            let count = export_table(conn, table_name, format, &mut out)?;
+            // Keep stdout clean for the exported data
+            eprintln!("Exported {} records from table '{}'.", count, table_name);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,147 @@
+// Synthetic country-audience datasets for benchmarking at scale. Rows follow the
+// shape of the FIFA data: confederations in their original proportions and
+// heavy-tailed shares fitted per confederation. The same seed gives the same file.
+use crate::{profile, FIFA_COLUMNS};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+pub const DEFAULT_SEED: u64 = 42;
+
+// Added before taking logs so zero shares stay finite
+const LOG_OFFSET: f64 = 0.05;
+
+struct Confederation {
+    name: &'static str,
+    // Countries in the original dataset
+    weight: u32,
+    // Mean of ln(population_share + LOG_OFFSET)
+    log_population: f64,
+    // Mean log ratio of TV audience and GDP-weighted share to population share
+    log_tv_ratio: f64,
+    log_gdp_ratio: f64,
+}
+
+// Fitted to data/fifa_countries_audience.csv
+const CONFEDERATIONS: [Confederation; 6] = [
+    Confederation {
+        name: "AFC",
+        weight: 43,
+        log_population: -1.08,
+        log_tv_ratio: -0.15,
+        log_gdp_ratio: -0.49,
+    },
+    Confederation {
+        name: "CAF",
+        weight: 50,
+        log_population: -1.54,
+        log_tv_ratio: -0.63,
+        log_gdp_ratio: -1.19,
+    },
+    Confederation {
+        name: "CONCACAF",
+        weight: 30,
+        log_population: -2.28,
+        log_tv_ratio: 0.15,
+        log_gdp_ratio: -0.01,
+    },
+    Confederation {
+        name: "CONMEBOL",
+        weight: 10,
+        log_population: -1.05,
+        log_tv_ratio: 0.71,
+        log_gdp_ratio: 0.35,
+    },
+    Confederation {
+        name: "OFC",
+        weight: 12,
+        log_population: -2.81,
+        log_tv_ratio: 0.0,
+        log_gdp_ratio: -0.09,
+    },
+    Confederation {
+        name: "UEFA",
+        weight: 46,
+        log_population: -1.73,
+        log_tv_ratio: 0.5,
+        log_gdp_ratio: 0.64,
+    },
+];
+
+// Spread of population shares within a confederation, and of each country's
+// audience and GDP shares around its population share
+const POPULATION_SIGMA: f64 = 1.2;
+const RATIO_SIGMA: f64 = 0.5;
+
+// Standard normal sample by the Box-Muller transform
+fn standard_normal(rng: &mut ChaCha8Rng) -> f64 {
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+// Undo the log offset and write one decimal, as in the original data
+fn share(log_value: f64) -> String {
+    format!("{:.1}", (log_value.exp() - LOG_OFFSET).max(0.0))
+}
+
+fn pick_confederation(rng: &mut ChaCha8Rng) -> &'static Confederation {
+    let total: u32 = CONFEDERATIONS.iter().map(|c| c.weight).sum();
+    let mut pick = rng.gen_range(0..total);
+    for confederation in &CONFEDERATIONS {
+        if pick < confederation.weight {
+            return confederation;
+        }
+        pick -= confederation.weight;
+    }
+    &CONFEDERATIONS[CONFEDERATIONS.len() - 1]
+}
+
+// Write `rows` synthetic rows, with a header, as CSV
+pub fn generate_rows(rows: usize, seed: u64, out: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut wtr = csv::Writer::from_writer(out);
+    wtr.write_record(FIFA_COLUMNS)?;
+    for i in 1..=rows {
+        let confederation = pick_confederation(&mut rng);
+        let log_population =
+            confederation.log_population + POPULATION_SIGMA * standard_normal(&mut rng);
+        let log_tv =
+            log_population + confederation.log_tv_ratio + RATIO_SIGMA * standard_normal(&mut rng);
+        let log_gdp =
+            log_population + confederation.log_gdp_ratio + RATIO_SIGMA * standard_normal(&mut rng);
+        wtr.write_record([
+            format!("Country {}", i),
+            confederation.name.to_string(),
+            share(log_population),
+            share(log_tv),
+            share(log_gdp),
+        ])?;
+    }
+    wtr.flush()?;
+    profile::add_rows(rows);
+    Ok(())
+}
+
+// Generate a CSV file of `rows` rows, creating its directory if needed
+pub fn generate_exec(file_path: &str, rows: usize, seed: u64) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = Path::new(file_path).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let start = Instant::now();
+    let mut out = BufWriter::new(File::create(file_path)?);
+    generate_rows(rows, seed, &mut out)?;
+    out.flush()?;
+    println!(
+        "Generated {} rows into '{}' with seed {} in {:.2} seconds.",
+        rows,
+        file_path,
+        seed,
+        start.elapsed().as_secs_f32()
+    );
+    Ok(())
+}
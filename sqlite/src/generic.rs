@@ -0,0 +1,260 @@
+// Generic dataset mode: infer a table's columns and types from a CSV header and
+// a sample of rows, so CSVs other than the FIFA dataset can be created and loaded.
+use crate::audit::{self, AuditOperation};
+use crate::load::{csv_reader, open_input, prepare_input, CsvOptions, STDIN_PATH};
+use crate::{profile, with_savepoint, DELETED_AT_COLUMN};
+use csv::StringRecord;
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection};
+use std::error::Error;
+use std::fmt;
+
+// Rows read before choosing column types when no other value is given
+pub const DEFAULT_SAMPLE_ROWS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Integer,
+    Real,
+    Text,
+}
+
+impl ColumnType {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            ColumnType::Integer => "INTEGER",
+            ColumnType::Real => "REAL",
+            ColumnType::Text => "TEXT",
+        }
+    }
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_sql())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnSpec {
+    pub name: String,
+    pub column_type: ColumnType,
+}
+
+// Turn a CSV header into a SQL identifier: lowercase letters, digits and
+// underscores, not starting with a digit
+pub fn column_name(header: &str) -> String {
+    let mut name = String::new();
+    for c in header.trim().chars() {
+        if c.is_ascii_alphanumeric() {
+            name.push(c.to_ascii_lowercase());
+        } else if !name.is_empty() && !name.ends_with('_') {
+            name.push('_');
+        }
+    }
+    let name = name.trim_end_matches('_').to_string();
+    if name.is_empty() {
+        "column".to_string()
+    } else if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+// Column names for a header row, made unique and kept clear of the
+// id and deleted_at columns the library manages itself
+fn unique_column_names(headers: &StringRecord) -> Vec<String> {
+    let mut names: Vec<String> = Vec::with_capacity(headers.len());
+    for header in headers {
+        let base = column_name(header);
+        let mut name = base.clone();
+        let mut suffix = 2;
+        while name == "id" || name == DELETED_AT_COLUMN || names.contains(&name) {
+            name = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+        names.push(name);
+    }
+    names
+}
+
+// Normalize a decimal comma to a point so the value parses as a number
+fn normalize_decimal(value: &str, decimal_separator: char) -> String {
+    if decimal_separator == '.' {
+        value.to_string()
+    } else {
+        value.replace(decimal_separator, ".")
+    }
+}
+
+// Narrowest type that fits every non-empty sample value
+fn infer_type<'a>(values: impl Iterator<Item = &'a str>, decimal_separator: char) -> ColumnType {
+    let mut column_type = ColumnType::Integer;
+    for value in values.filter(|v| !v.trim().is_empty()) {
+        let value = normalize_decimal(value.trim(), decimal_separator);
+        if column_type == ColumnType::Integer && value.parse::<i64>().is_err() {
+            column_type = ColumnType::Real;
+        }
+        if column_type == ColumnType::Real && value.parse::<f64>().is_err() {
+            return ColumnType::Text;
+        }
+    }
+    column_type
+}
+
+// Infer column names from the header and column types from the sample rows.
+// Columns that are empty in every sample row are TEXT.
+pub fn infer_schema(
+    headers: &StringRecord,
+    sample: &[StringRecord],
+    decimal_separator: char,
+) -> Vec<ColumnSpec> {
+    unique_column_names(headers)
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let values = || sample.iter().filter_map(|record| record.get(i));
+            let column_type = if values().all(|v| v.trim().is_empty()) {
+                ColumnType::Text
+            } else {
+                infer_type(values(), decimal_separator)
+            };
+            ColumnSpec { name, column_type }
+        })
+        .collect()
+}
+
+// Create a table with an id key and the given columns
+pub fn create_table_from_schema(
+    conn: &Connection,
+    table_name: &str,
+    columns: &[ColumnSpec],
+) -> Result<(), Box<dyn Error>> {
+    let definitions: Vec<String> = columns
+        .iter()
+        .map(|column| format!("{} {}", column.name, column.column_type))
+        .collect();
+    let create_query = format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            {}
+        )",
+        table_name,
+        definitions.join(",\n            ")
+    );
+    conn.execute(&create_query, [])?;
+    println!("Table '{}' created successfully.", table_name);
+    Ok(())
+}
+
+// Convert a CSV field to a SQL value of the column's type.
+// Empty fields become NULL; fields that do not fit the inferred type are kept as text.
+fn field_value(field: &str, column_type: ColumnType, decimal_separator: char) -> Value {
+    let trimmed = field.trim();
+    if trimmed.is_empty() {
+        return Value::Null;
+    }
+    let number = normalize_decimal(trimmed, decimal_separator);
+    match column_type {
+        ColumnType::Integer => match number.parse::<i64>() {
+            Ok(i) => Value::Integer(i),
+            Err(_) => Value::Text(field.to_string()),
+        },
+        ColumnType::Real => match number.parse::<f64>() {
+            Ok(f) => Value::Real(f),
+            Err(_) => Value::Text(field.to_string()),
+        },
+        ColumnType::Text => Value::Text(field.to_string()),
+    }
+}
+
+// Infer a schema from a CSV file (or "-" for stdin), create a matching table
+// and load every row into it. Returns the inferred columns.
+pub fn load_generic_csv(
+    conn: &Connection,
+    table_name: &str,
+    file_path: &str,
+    csv_options: &CsvOptions,
+    sample_rows: usize,
+) -> Result<Vec<ColumnSpec>, Box<dyn Error>> {
+    let reader = prepare_input(open_input(file_path)?, csv_options.strip_bom)?;
+    let mut rdr = csv_reader(reader, csv_options);
+
+    let headers = if csv_options.has_headers {
+        rdr.headers()?.clone()
+    } else {
+        let width = rdr.headers()?.len();
+        (1..=width).map(|i| format!("column_{}", i)).collect()
+    };
+    if headers.is_empty() {
+        return Err(format!("{}: no columns to load", file_path).into());
+    }
+
+    // Sample rows are read once and then loaded ahead of the rest of the file
+    let mut sample = Vec::with_capacity(sample_rows);
+    let mut records = rdr.records();
+    for result in records.by_ref().take(sample_rows) {
+        sample.push(result?);
+    }
+    let columns = infer_schema(&headers, &sample, csv_options.decimal_separator);
+
+    let names: Vec<&str> = columns.iter().map(|c| c.name.as_str()).collect();
+    let placeholders = vec!["?"; columns.len()].join(", ");
+    let insert_query = format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        table_name,
+        names.join(", "),
+        placeholders
+    );
+
+    // A failed load also takes back the table it created
+    let count = with_savepoint(conn, || {
+        create_table_from_schema(conn, table_name, &columns)?;
+        let op_id = audit::begin_operation(conn)?;
+        let mut insert_stmt = conn.prepare(&insert_query)?;
+        let mut count = 0;
+        for result in sample.into_iter().map(Ok).chain(records) {
+            let record = result.map_err(|e| format!("record {}: {}", count + 1, e))?;
+            let values = columns.iter().enumerate().map(|(i, column)| {
+                field_value(
+                    record.get(i).unwrap_or(""),
+                    column.column_type,
+                    csv_options.decimal_separator,
+                )
+            });
+            insert_stmt.execute(params_from_iter(values))?;
+
+            let id = conn.last_insert_rowid();
+            let after = audit::record_image(conn, table_name, id)?;
+            audit::write_entry(
+                conn,
+                op_id,
+                AuditOperation::Load,
+                table_name,
+                id,
+                None,
+                after.as_deref(),
+            )?;
+            count += 1;
+        }
+        profile::add_rows(count);
+        Ok(count)
+    })?;
+
+    let source = if file_path == STDIN_PATH {
+        "stdin"
+    } else {
+        file_path
+    };
+    let summary: Vec<String> = columns
+        .iter()
+        .map(|c| format!("{} {}", c.name, c.column_type))
+        .collect();
+    println!("Inferred columns: {}", summary.join(", "));
+    println!(
+        "Data loaded successfully from '{}' into table '{}' ({} records).",
+        source, table_name, count
+    );
+    Ok(columns)
+}
@@ -1,72 +1,153 @@
-use csv::ReaderBuilder; //for loading from csv
 use reqwest::blocking;
+use rusqlite::types::Value;
 use rusqlite::ToSql;
 use rusqlite::{params, Connection, Result};
 use std::error::Error;
+use std::fmt;
 use std::fs::{create_dir_all, File};
 use std::io::copy;
+use std::ops::ControlFlow;
 use std::path::Path;
 
-// Extract a url to a file path
+pub mod audit;
+pub mod backup;
+pub mod compare;
+pub mod compression;
+pub mod config;
+pub mod connection;
+pub mod export;
+pub mod generate;
+pub mod generic;
+pub mod load;
+pub mod pipeline;
+pub mod profile;
+pub mod report;
+pub mod schema;
+pub mod script;
+pub mod sql;
+
+pub use load::{
+    load_data, load_data_from_csv, load_data_from_json, load_data_from_ndjson,
+    load_data_from_reader, parse_csv_byte, parse_decimal_separator, CsvOptions, LoadFormat,
+    LoadOptions,
+};
+
+use audit::AuditOperation;
+
+// Data columns of the country table, in CSV order
+pub const FIFA_COLUMNS: [&str; 5] = [
+    "country",
+    "confederation",
+    "population_share",
+    "tv_audience_share",
+    "gdp_weighted_share",
+];
+
+// Column marking a soft-deleted record with the time it was deleted
+pub const DELETED_AT_COLUMN: &str = "deleted_at";
+
+// Run `f` inside a savepoint so a change and its audit entries are written together.
+// Savepoints nest, so this also works when the caller already holds a transaction.
+pub(crate) fn with_savepoint<T>(
+    conn: &Connection,
+    f: impl FnOnce() -> Result<T, Box<dyn Error>>,
+) -> Result<T, Box<dyn Error>> {
+    conn.execute_batch("SAVEPOINT sqlite_change")?;
+    match f() {
+        Ok(value) => {
+            conn.execute_batch("RELEASE sqlite_change")?;
+            Ok(value)
+        }
+        Err(e) => {
+            conn.execute_batch("ROLLBACK TO sqlite_change; RELEASE sqlite_change")?;
+            Err(e)
+        }
+    }
+}
+
+// Source of the FIFA audience dataset and where extract() saves it
+pub const FIFA_URL: &str = "https://raw.githubusercontent.com/fivethirtyeight/data/refs/heads/master/fifa/fifa_countries_audience.csv";
+pub const FIFA_FILE_PATH: &str = "data/fifa_countries_audience.csv";
+
+// Extract the FIFA dataset to its default file path
 pub fn extract() -> Result<(), Box<dyn Error>> {
-    let url = "https://raw.githubusercontent.com/fivethirtyeight/data/refs/heads/master/fifa/fifa_countries_audience.csv";
-    let directory = "data";
-    let file_path = "data/fifa_countries_audience.csv";
+    extract_from(FIFA_URL, FIFA_FILE_PATH)
+}
 
+// Extract a url to a file path, decompressing .gz and .zip resources on the way
+pub fn extract_from(url: &str, file_path: &str) -> Result<(), Box<dyn Error>> {
     // Create the directory if it doesn't exist
-    if !Path::new(directory).exists() {
-        create_dir_all(directory)?;
+    if let Some(directory) = Path::new(file_path).parent() {
+        if !directory.as_os_str().is_empty() && !directory.exists() {
+            create_dir_all(directory)?;
+        }
     }
 
-    let response = blocking::get(url)?;
-    let mut dest = File::create(file_path)?;
+    let response = blocking::get(url)?.error_for_status()?;
     let content = response.bytes()?;
-    copy(&mut content.as_ref(), &mut dest)?;
+    let mut reader = compression::decompressed(content.as_ref())?;
+    let mut dest = File::create(file_path)?;
+    copy(&mut reader, &mut dest)?;
 
     println!("File has been downloaded to {}", file_path);
     Ok(())
 }
 
-// Load data from a file path to a table
-pub fn load_data_from_csv(
+// Default file path for an extracted url: its file name under data/,
+// without the compression extension
+pub fn extract_file_path(url: &str) -> String {
+    let name = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("download.csv");
+    let name = if let Some(stem) = name.strip_suffix(".gz") {
+        stem.to_string()
+    } else if let Some(stem) = name.strip_suffix(".zip") {
+        format!("{}.csv", stem)
+    } else {
+        name.to_string()
+    };
+    format!("data/{}", name)
+}
+
+// Check whether a table has a column
+pub(crate) fn has_column(
     conn: &Connection,
     table_name: &str,
-    file_path: &str,
-) -> Result<(), Box<dyn Error>> {
-    //Box<dyn Error> is a trait object that can represent any error type
-    let file = File::open(file_path)?;
-    let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(file);
-
-    let insert_query = format!(
-        "INSERT INTO {} (country,confederation,population_share,tv_audience_share,gdp_weighted_share) VALUES (?, ?, ?, ?, ?)",
-        table_name
-    );
-
-    for result in rdr.records() {
-        let record = result?;
-        let country = &record[0];
-        let confederation = &record[1];
-        let population_share: f64 = record[2].parse()?;
-        let tv_audience_share: f64 = record[3].parse()?;
-        let gdp_weighted_share: f64 = record[4].parse()?;
+    column: &str,
+) -> Result<bool, Box<dyn Error>> {
+    let exists = conn
+        .prepare_cached("SELECT COUNT(*) > 0 FROM pragma_table_info(?) WHERE name = ?")?
+        .query_row(params![table_name, column], |row| row.get(0))?;
+    Ok(exists)
+}
 
-        conn.execute(
-            &insert_query,
-            params![
-                country,
-                confederation,
-                population_share,
-                tv_audience_share,
-                gdp_weighted_share
-            ],
-        )?;
+// Columns holding a table's data, in declaration order, leaving out
+// the id key and the soft-delete marker
+pub fn data_columns(conn: &Connection, table_name: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let columns = conn
+        .prepare_cached("SELECT name FROM pragma_table_info(?) ORDER BY cid")?
+        .query_map(params![table_name], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    if columns.is_empty() {
+        return Err(format!("no such table: {}", table_name).into());
     }
+    Ok(columns
+        .into_iter()
+        .filter(|column| column != "id" && column != DELETED_AT_COLUMN)
+        .collect())
+}
 
-    println!(
-        "Data loaded successfully from '{}' into table '{}'.",
-        file_path, table_name
-    );
-    Ok(())
+// SQL condition matching records that are not soft-deleted.
+// Reads and aggregates add this to their WHERE clause.
+pub fn live_rows_condition(conn: &Connection, table_name: &str) -> Result<String, Box<dyn Error>> {
+    if has_column(conn, table_name, DELETED_AT_COLUMN)? {
+        Ok(format!("{} IS NULL", DELETED_AT_COLUMN))
+    } else {
+        Ok("1 = 1".to_string())
+    }
 }
 
 // Create a table
@@ -90,70 +171,233 @@ pub fn create_table(conn: &Connection, table_name: &str) -> Result<(), Box<dyn E
 // Drop a table
 pub fn drop_table(conn: &Connection, table_name: &str) -> Result<(), Box<dyn Error>> {
     let drop_query = format!("DROP TABLE IF EXISTS {}", table_name);
-    conn.execute(&drop_query, [])?;
+    with_savepoint(conn, || {
+        conn.execute(&drop_query, [])?;
+        audit::record_drop(conn, table_name)
+    })?;
     println!("Table '{}' dropped successfully.", table_name);
     Ok(())
 }
 
-// Read records in table
-pub fn read_exec(conn: &Connection, table_name: &str) -> Result<(), Box<dyn Error>> {
-    let query_string = format!("SELECT * FROM {}", table_name);
+// Render a nullable column value for printing
+fn display_or_null<T: std::fmt::Display>(value: Option<T>) -> String {
+    match value {
+        Some(v) => v.to_string(),
+        None => "NULL".to_string(),
+    }
+}
+
+// Render any column value for printing
+fn display_value(value: Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Integer(i) => i.to_string(),
+        Value::Real(f) => f.to_string(),
+        Value::Text(s) => s,
+        Value::Blob(b) => format!("<{} bytes>", b.len()),
+    }
+}
+
+// Rows printed by read_exec when no limit is given
+pub const DEFAULT_READ_LIMIT: usize = 10;
+
+// Count the rows of a table that match a condition
+pub(crate) fn count_rows(
+    conn: &Connection,
+    table_name: &str,
+    condition: &str,
+) -> Result<usize, Box<dyn Error>> {
+    let count: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM {} WHERE {}", table_name, condition),
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count as usize)
+}
+
+// Print a note on the rows left out once `shown` rows have been printed
+fn print_rows_not_shown(
+    conn: &Connection,
+    table_name: &str,
+    condition: &str,
+    shown: usize,
+) -> Result<(), Box<dyn Error>> {
+    let total = count_rows(conn, table_name, condition)?;
+    if total > shown {
+        println!("...\nand {} more rows not shown", total - shown);
+    }
+    Ok(())
+}
+
+// Print the first rows of a table with arbitrary columns as "name: value" pairs
+fn read_generic_rows(
+    conn: &Connection,
+    table_name: &str,
+    condition: &str,
+    limit: usize,
+) -> Result<(), Box<dyn Error>> {
+    let query_string = format!("SELECT * FROM {} WHERE {}", table_name, condition);
     let mut stmt = conn.prepare(&query_string)?;
+    let names: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let mut rows = stmt.query([])?;
 
-    // Use query_map to handle multiple rows
-    let rows: Vec<_> = stmt
-        .query_map([], |row| {
-            let id: i32 = row.get(0)?;
-            let country: String = row.get(1)?;
-            let confederation: String = row.get(2)?;
-            let population_share: f64 = row.get(3)?;
-            let tv_audience_share: f64 = row.get(4)?;
-            let gdp_weighted_share: f64 = row.get(5)?;
-            Ok((
-                id,
-                country,
-                confederation,
-                population_share,
-                tv_audience_share,
-                gdp_weighted_share,
-            ))
-        })?
-        .collect::<Result<Vec<_>, _>>()?;
+    let mut count = 0;
+    while count < limit {
+        let Some(row) = rows.next()? else {
+            break;
+        };
+        let mut fields = Vec::with_capacity(names.len());
+        for (i, name) in names.iter().enumerate() {
+            fields.push(format!("{}: {}", name, display_value(row.get(i)?)));
+        }
+        println!("{}", fields.join(", "));
+        count += 1;
+    }
 
-    // Iterate over the rows and print the results
-    // for row in rows {
-    //     let (id, country, confederation, population_share, tv_audience_share, gdp_weighted_share) =
-    //         row?;
-    //     println!(
-    //         "ID: {}, Country: {}, Confederation: {}, Population Share: {}, TV Audience Share: {}, GDP Weighted Share: {}",
-    //         id, country, confederation, population_share, tv_audience_share, gdp_weighted_share
-    //     );
-    // }
-
-    // Limit output to first 10 rows
-    let max_rows = 10;
-    for row in rows.iter().take(max_rows) {
-        let (id, country, confederation, population_share, tv_audience_share, gdp_weighted_share) =
-            row;
-        println!(
+    profile::add_rows(count);
+    print_rows_not_shown(conn, table_name, condition, count)
+}
+
+// One record of a table with the FIFA schema. Text and share columns may be
+// NULL after an update clears them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FifaCountry {
+    pub id: i32,
+    pub country: Option<String>,
+    pub confederation: Option<String>,
+    pub population_share: Option<f64>,
+    pub tv_audience_share: Option<f64>,
+    pub gdp_weighted_share: Option<f64>,
+    // Set when the record is soft-deleted
+    pub deleted_at: Option<String>,
+}
+
+impl fmt::Display for FifaCountry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
             "ID: {}, Country: {}, Confederation: {}, Population Share: {}, TV Audience Share: {}, GDP Weighted Share: {}",
-            id, country, confederation, population_share, tv_audience_share, gdp_weighted_share
-        );
+            self.id,
+            display_or_null(self.country.as_deref()),
+            display_or_null(self.confederation.as_deref()),
+            display_or_null(self.population_share),
+            display_or_null(self.tv_audience_share),
+            display_or_null(self.gdp_weighted_share)
+        )?;
+        if let Some(deleted_at) = &self.deleted_at {
+            write!(f, ", Deleted At: {}", deleted_at)?;
+        }
+        Ok(())
     }
+}
 
-    if rows.len() > max_rows {
-        println!("...\nand {} more rows not shown", rows.len() - max_rows);
+// Call `f` with each record of a FIFA-schema table until it returns
+// ControlFlow::Break. Rows are read one at a time, so memory stays flat however
+// large the table is. Returns the number of records passed to `f`.
+pub fn for_each_country<F>(
+    conn: &Connection,
+    table_name: &str,
+    include_deleted: bool,
+    mut f: F,
+) -> Result<usize, Box<dyn Error>>
+where
+    F: FnMut(FifaCountry) -> ControlFlow<()>,
+{
+    let has_deleted_at = has_column(conn, table_name, DELETED_AT_COLUMN)?;
+    let condition = if include_deleted {
+        "1 = 1".to_string()
+    } else {
+        live_rows_condition(conn, table_name)?
+    };
+    let query_string = format!(
+        "SELECT id, {}, {} FROM {} WHERE {}",
+        FIFA_COLUMNS.join(", "),
+        if has_deleted_at {
+            DELETED_AT_COLUMN
+        } else {
+            "NULL"
+        },
+        table_name,
+        condition
+    );
+    let mut stmt = conn.prepare(&query_string)?;
+    let mut rows = stmt.query([])?;
+
+    let mut count = 0;
+    while let Some(row) = rows.next()? {
+        let record = FifaCountry {
+            id: row.get(0)?,
+            country: row.get(1)?,
+            confederation: row.get(2)?,
+            population_share: row.get(3)?,
+            tv_audience_share: row.get(4)?,
+            gdp_weighted_share: row.get(5)?,
+            deleted_at: row.get(6)?,
+        };
+        count += 1;
+        if f(record).is_break() {
+            break;
+        }
     }
+    profile::add_rows(count);
+    Ok(count)
+}
 
-    Ok(())
+// Options for reading records
+#[derive(Debug, Default)]
+pub struct ReadOptions {
+    pub include_deleted: bool,
+    // Rows to print; DEFAULT_READ_LIMIT when not given
+    pub limit: Option<usize>,
+}
+
+// Read records in table, leaving out soft-deleted records
+pub fn read_exec(conn: &Connection, table_name: &str) -> Result<(), Box<dyn Error>> {
+    read_exec_with_options(conn, table_name, &ReadOptions::default())
+}
+
+// Read records in table, printing up to the limit. Reading stops at the limit
+// and the remaining rows are only counted.
+pub fn read_exec_with_options(
+    conn: &Connection,
+    table_name: &str,
+    options: &ReadOptions,
+) -> Result<(), Box<dyn Error>> {
+    let limit = options.limit.unwrap_or(DEFAULT_READ_LIMIT);
+    let condition = if options.include_deleted {
+        "1 = 1".to_string()
+    } else {
+        live_rows_condition(conn, table_name)?
+    };
+    // Tables loaded in generic mode have their own columns
+    if data_columns(conn, table_name)? != FIFA_COLUMNS {
+        return read_generic_rows(conn, table_name, &condition, limit);
+    }
+
+    let mut shown = 0;
+    if limit > 0 {
+        for_each_country(conn, table_name, options.include_deleted, |record| {
+            println!("{}", record);
+            shown += 1;
+            if shown < limit {
+                ControlFlow::Continue(())
+            } else {
+                ControlFlow::Break(())
+            }
+        })?;
+    }
+    print_rows_not_shown(conn, table_name, &condition, shown)
 }
 
+// New values for an update: None leaves a column unchanged,
+// Some(None) clears it to NULL and Some(Some(v)) sets it to v
+#[derive(Debug, Default)]
 pub struct UpdateFields<'a> {
-    pub new_country: Option<&'a str>,
-    pub new_confederation: Option<&'a str>,
-    pub new_population_share: Option<f64>,
-    pub new_tv_audience_share: Option<f64>,
-    pub new_gdp_weighted_share: Option<f64>,
+    pub new_country: Option<Option<&'a str>>,
+    pub new_confederation: Option<Option<&'a str>>,
+    pub new_population_share: Option<Option<f64>>,
+    pub new_tv_audience_share: Option<Option<f64>>,
+    pub new_gdp_weighted_share: Option<Option<f64>>,
 }
 
 // Update a record in the table
@@ -168,11 +412,11 @@ pub fn update_exec(
 
     if let Some(country) = fields.new_country {
         updates.push("country = ?");
-        params.push(Box::new(country.to_string()));
+        params.push(Box::new(country.map(str::to_string)));
     }
     if let Some(confederation) = fields.new_confederation {
         updates.push("confederation = ?");
-        params.push(Box::new(confederation.to_string()));
+        params.push(Box::new(confederation.map(str::to_string)));
     }
     if let Some(population_share) = fields.new_population_share {
         updates.push("population_share = ?");
@@ -199,14 +443,31 @@ pub fn update_exec(
     );
     params.push(Box::new(id));
 
-    conn.execute(
-        &update_query,
-        params
-            .iter()
-            .map(|b| &**b)
-            .collect::<Vec<&dyn ToSql>>()
-            .as_slice(),
-    )?;
+    with_savepoint(conn, || {
+        let before = audit::record_image(conn, table_name, id.into())?;
+        conn.execute(
+            &update_query,
+            params
+                .iter()
+                .map(|b| &**b)
+                .collect::<Vec<&dyn ToSql>>()
+                .as_slice(),
+        )?;
+        if before.is_some() {
+            let op_id = audit::begin_operation(conn)?;
+            let after = audit::record_image(conn, table_name, id.into())?;
+            audit::write_entry(
+                conn,
+                op_id,
+                AuditOperation::Update,
+                table_name,
+                id.into(),
+                before.as_deref(),
+                after.as_deref(),
+            )?;
+        }
+        Ok(())
+    })?;
 
     println!(
         "Record with ID '{}' updated successfully in table '{}'.",
@@ -230,16 +491,30 @@ pub fn create_exec(
         table_name
     );
 
-    conn.execute(
-        &insert_query,
-        params![
-            country,
-            confederation,
-            population_share,
-            tv_audience_share,
-            gdp_weighted_share
-        ],
-    )?;
+    with_savepoint(conn, || {
+        conn.execute(
+            &insert_query,
+            params![
+                country,
+                confederation,
+                population_share,
+                tv_audience_share,
+                gdp_weighted_share
+            ],
+        )?;
+        let id = conn.last_insert_rowid();
+        let op_id = audit::begin_operation(conn)?;
+        let after = audit::record_image(conn, table_name, id)?;
+        audit::write_entry(
+            conn,
+            op_id,
+            AuditOperation::Insert,
+            table_name,
+            id,
+            None,
+            after.as_deref(),
+        )
+    })?;
     println!(
         "Inserted record into the '{}' table successfully!",
         table_name
@@ -250,10 +525,177 @@ pub fn create_exec(
 // Delete a record in the table
 pub fn delete_exec(conn: &Connection, table_name: &str, id: i32) -> Result<(), Box<dyn Error>> {
     let delete_query = format!("DELETE FROM {} WHERE id = ?", table_name);
-    conn.execute(&delete_query, params![id])?;
+    with_savepoint(conn, || {
+        let before = audit::record_image(conn, table_name, id.into())?;
+        conn.execute(&delete_query, params![id])?;
+        if before.is_some() {
+            let op_id = audit::begin_operation(conn)?;
+            audit::write_entry(
+                conn,
+                op_id,
+                AuditOperation::Delete,
+                table_name,
+                id.into(),
+                before.as_deref(),
+                None,
+            )?;
+        }
+        Ok(())
+    })?;
     println!(
         "Deleted person with ID '{}' from the '{}' table successfully!",
         id, table_name
     );
     Ok(())
 }
+
+// Add the deleted_at column used by soft deletes, if the table does not have it yet
+fn ensure_deleted_at_column(conn: &Connection, table_name: &str) -> Result<(), Box<dyn Error>> {
+    if !has_column(conn, table_name, DELETED_AT_COLUMN)? {
+        conn.execute(
+            &format!(
+                "ALTER TABLE {} ADD COLUMN {} TEXT",
+                table_name, DELETED_AT_COLUMN
+            ),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+// Mark a record as deleted without removing it, so it can be restored later
+pub fn soft_delete_exec(
+    conn: &Connection,
+    table_name: &str,
+    id: i32,
+) -> Result<(), Box<dyn Error>> {
+    let soft_delete_query = format!(
+        "UPDATE {} SET {} = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ? AND {} IS NULL",
+        table_name, DELETED_AT_COLUMN, DELETED_AT_COLUMN
+    );
+    let changed = with_savepoint(conn, || {
+        ensure_deleted_at_column(conn, table_name)?;
+        let before = audit::record_image(conn, table_name, id.into())?;
+        let changed = conn.execute(&soft_delete_query, params![id])?;
+        if changed > 0 {
+            let op_id = audit::begin_operation(conn)?;
+            let after = audit::record_image(conn, table_name, id.into())?;
+            audit::write_entry(
+                conn,
+                op_id,
+                AuditOperation::SoftDelete,
+                table_name,
+                id.into(),
+                before.as_deref(),
+                after.as_deref(),
+            )?;
+        }
+        Ok(changed)
+    })?;
+
+    if changed == 0 {
+        println!(
+            "No live record with ID '{}' in the '{}' table to delete.",
+            id, table_name
+        );
+    } else {
+        println!(
+            "Soft-deleted record with ID '{}' from the '{}' table successfully!",
+            id, table_name
+        );
+    }
+    Ok(())
+}
+
+// Bring back a soft-deleted record
+pub fn restore_exec(conn: &Connection, table_name: &str, id: i32) -> Result<(), Box<dyn Error>> {
+    if !has_column(conn, table_name, DELETED_AT_COLUMN)? {
+        println!("Table '{}' has no soft-deleted records.", table_name);
+        return Ok(());
+    }
+
+    let restore_query = format!(
+        "UPDATE {} SET {} = NULL WHERE id = ? AND {} IS NOT NULL",
+        table_name, DELETED_AT_COLUMN, DELETED_AT_COLUMN
+    );
+    let changed = with_savepoint(conn, || {
+        let before = audit::record_image(conn, table_name, id.into())?;
+        let changed = conn.execute(&restore_query, params![id])?;
+        if changed > 0 {
+            let op_id = audit::begin_operation(conn)?;
+            let after = audit::record_image(conn, table_name, id.into())?;
+            audit::write_entry(
+                conn,
+                op_id,
+                AuditOperation::Restore,
+                table_name,
+                id.into(),
+                before.as_deref(),
+                after.as_deref(),
+            )?;
+        }
+        Ok(changed)
+    })?;
+
+    if changed == 0 {
+        println!(
+            "No soft-deleted record with ID '{}' in the '{}' table.",
+            id, table_name
+        );
+    } else {
+        println!(
+            "Restored record with ID '{}' in the '{}' table successfully!",
+            id, table_name
+        );
+    }
+    Ok(())
+}
+
+// Permanently remove soft-deleted records, either one by id or all of them
+pub fn purge_exec(
+    conn: &Connection,
+    table_name: &str,
+    id: Option<i32>,
+) -> Result<(), Box<dyn Error>> {
+    if !has_column(conn, table_name, DELETED_AT_COLUMN)? {
+        println!("Table '{}' has no soft-deleted records.", table_name);
+        return Ok(());
+    }
+
+    let purged = with_savepoint(conn, || {
+        let select_query = format!(
+            "SELECT id FROM {} WHERE {} IS NOT NULL AND (?1 IS NULL OR id = ?1)",
+            table_name, DELETED_AT_COLUMN
+        );
+        let ids = conn
+            .prepare(&select_query)?
+            .query_map(params![id], |row| row.get::<_, i64>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        let op_id = audit::begin_operation(conn)?;
+        let delete_query = format!("DELETE FROM {} WHERE id = ?", table_name);
+        for record_id in &ids {
+            let before = audit::record_image(conn, table_name, *record_id)?;
+            conn.execute(&delete_query, params![record_id])?;
+            audit::write_entry(
+                conn,
+                op_id,
+                AuditOperation::Purge,
+                table_name,
+                *record_id,
+                before.as_deref(),
+                None,
+            )?;
+        }
+        Ok(ids.len())
+    })?;
+
+    println!(
+        "Purged {} soft-deleted record(s) from the '{}' table.",
+        purged, table_name
+    );
+    Ok(())
+}
@@ -0,0 +1,444 @@
+// Load records into the country table from CSV, JSON and NDJSON files.
+// Every format goes through insert_records, so rows are validated the same way
+// and each load is written in one savepoint together with its audit entries.
+use crate::audit::{self, AuditOperation};
+use crate::compression::decompressed;
+use crate::{profile, with_savepoint, FIFA_COLUMNS};
+use csv::{ReaderBuilder, StringRecord, Trim}; //for loading from csv
+use rusqlite::{params, Connection};
+use serde_json::Value;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoadFormat {
+    #[default]
+    Csv,
+    Json,
+    Ndjson,
+}
+
+impl LoadFormat {
+    // Guess the format from a file extension, looking through .gz and .zip
+    pub fn from_path(path: &str) -> Option<LoadFormat> {
+        let path = Path::new(path);
+        let mut extension = path.extension()?.to_str()?;
+        if extension == "gz" || extension == "zip" {
+            extension = Path::new(path.file_stem()?).extension()?.to_str()?;
+        }
+        extension.parse().ok()
+    }
+}
+
+impl FromStr for LoadFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(LoadFormat::Csv),
+            "json" => Ok(LoadFormat::Json),
+            "ndjson" | "jsonl" => Ok(LoadFormat::Ndjson),
+            other => Err(format!(
+                "unknown load format '{}' (expected csv, json or ndjson)",
+                other
+            )),
+        }
+    }
+}
+
+// CSV dialect settings; the defaults read the fivethirtyeight files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    pub has_headers: bool,
+    pub quote: u8,
+    // Lines starting with this byte are skipped
+    pub comment: Option<u8>,
+    // Trim whitespace around fields
+    pub trim: bool,
+    // Decimal separator used in the share columns, '.' or ','
+    pub decimal_separator: char,
+    // Skip a UTF-8 byte order mark at the start of the input
+    pub strip_bom: bool,
+}
+
+impl CsvOptions {
+    // Reject settings that cannot be told apart in a record
+    pub fn validate(&self) -> Result<(), String> {
+        if self.decimal_separator == char::from(self.delimiter) {
+            return Err(format!(
+                "the decimal separator '{}' cannot also be the delimiter",
+                self.decimal_separator
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: b',',
+            has_headers: true,
+            quote: b'"',
+            comment: None,
+            trim: false,
+            decimal_separator: '.',
+            strip_bom: true,
+        }
+    }
+}
+
+// Options for loading a file
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    pub format: LoadFormat,
+    // Only used for CSV input
+    pub csv: CsvOptions,
+}
+
+// Parse a single-byte CSV setting such as a delimiter, accepting a few names
+// for characters that are awkward to type ("tab", "semicolon", ...)
+pub fn parse_csv_byte(s: &str) -> Result<u8, String> {
+    match s {
+        "tab" | "\\t" | "\t" => Ok(b'\t'),
+        "comma" => Ok(b','),
+        "semicolon" => Ok(b';'),
+        "pipe" => Ok(b'|'),
+        "space" => Ok(b' '),
+        _ if s.len() == 1 && s.is_ascii() => Ok(s.as_bytes()[0]),
+        _ => Err(format!(
+            "expected a single ASCII character or tab, comma, semicolon, pipe or space, found '{}'",
+            s
+        )),
+    }
+}
+
+// Parse a decimal separator, which must be '.' or ','
+pub fn parse_decimal_separator(s: &str) -> Result<char, String> {
+    match s {
+        "." | "dot" => Ok('.'),
+        "," | "comma" => Ok(','),
+        _ => Err(format!(
+            "expected '.' or ',' as decimal separator, found '{}'",
+            s
+        )),
+    }
+}
+
+// One validated row of the country table; None is stored as NULL
+struct LoadRecord {
+    country: Option<String>,
+    confederation: Option<String>,
+    population_share: Option<f64>,
+    tv_audience_share: Option<f64>,
+    gdp_weighted_share: Option<f64>,
+}
+
+// Parse a share column, naming the column if it is not a number.
+// An empty value is NULL, as export writes it.
+fn parse_share(column: &str, value: &str) -> Result<Option<f64>, Box<dyn Error>> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+    value
+        .parse()
+        .map(Some)
+        .map_err(|e| format!("invalid {} '{}': {}", column, value, e).into())
+}
+
+// An empty CSV field is NULL, as export writes it
+fn csv_text(value: &str) -> Option<String> {
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+impl LoadRecord {
+    // Columns are read by position, in FIFA_COLUMNS order
+    fn from_csv(
+        record: &StringRecord,
+        decimal_separator: char,
+    ) -> Result<LoadRecord, Box<dyn Error>> {
+        if record.len() < FIFA_COLUMNS.len() {
+            return Err(format!(
+                "expected {} columns, found {}",
+                FIFA_COLUMNS.len(),
+                record.len()
+            )
+            .into());
+        }
+        let share = |i: usize| {
+            if decimal_separator == '.' {
+                parse_share(FIFA_COLUMNS[i], &record[i])
+            } else {
+                parse_share(FIFA_COLUMNS[i], &record[i].replace(decimal_separator, "."))
+            }
+        };
+        Ok(LoadRecord {
+            country: csv_text(&record[0]),
+            confederation: csv_text(&record[1]),
+            population_share: share(2)?,
+            tv_audience_share: share(3)?,
+            gdp_weighted_share: share(4)?,
+        })
+    }
+
+    // Columns are read by key; keys that are not columns (such as "id") are ignored
+    fn from_json(value: &Value) -> Result<LoadRecord, Box<dyn Error>> {
+        let object = value.as_object().ok_or("expected a JSON object")?;
+        let text = |column: &str| -> Result<Option<String>, Box<dyn Error>> {
+            match object.get(column) {
+                Some(Value::String(s)) => Ok(Some(s.clone())),
+                Some(Value::Null) => Ok(None),
+                Some(other) => {
+                    Err(format!("invalid {} {}: expected a string", column, other).into())
+                }
+                None => Err(format!("missing {}", column).into()),
+            }
+        };
+        let share = |column: &str| -> Result<Option<f64>, Box<dyn Error>> {
+            match object.get(column) {
+                Some(Value::Number(n)) => n
+                    .as_f64()
+                    .map(Some)
+                    .ok_or_else(|| format!("invalid {} {}", column, n).into()),
+                Some(Value::Null) => Ok(None),
+                Some(Value::String(s)) => parse_share(column, s),
+                Some(other) => {
+                    Err(format!("invalid {} {}: expected a number", column, other).into())
+                }
+                None => Err(format!("missing {}", column).into()),
+            }
+        };
+        Ok(LoadRecord {
+            country: text(FIFA_COLUMNS[0])?,
+            confederation: text(FIFA_COLUMNS[1])?,
+            population_share: share(FIFA_COLUMNS[2])?,
+            tv_audience_share: share(FIFA_COLUMNS[3])?,
+            gdp_weighted_share: share(FIFA_COLUMNS[4])?,
+        })
+    }
+}
+
+// Insert records in a single savepoint, auditing each one as part of one load.
+// Any invalid record rolls back the whole load.
+fn insert_records<I>(
+    conn: &Connection,
+    table_name: &str,
+    records: I,
+) -> Result<usize, Box<dyn Error>>
+where
+    I: Iterator<Item = Result<LoadRecord, Box<dyn Error>>>,
+{
+    let insert_query = format!(
+        "INSERT INTO {} ({}) VALUES (?, ?, ?, ?, ?)",
+        table_name,
+        FIFA_COLUMNS.join(",")
+    );
+
+    with_savepoint(conn, || {
+        let op_id = audit::begin_operation(conn)?;
+        let mut insert_stmt = conn.prepare(&insert_query)?;
+        let mut count = 0;
+        for (index, result) in records.enumerate() {
+            let record = result.map_err(|e| format!("record {}: {}", index + 1, e))?;
+            insert_stmt.execute(params![
+                record.country,
+                record.confederation,
+                record.population_share,
+                record.tv_audience_share,
+                record.gdp_weighted_share
+            ])?;
+
+            let id = conn.last_insert_rowid();
+            let after = audit::record_image(conn, table_name, id)?;
+            audit::write_entry(
+                conn,
+                op_id,
+                AuditOperation::Load,
+                table_name,
+                id,
+                None,
+                after.as_deref(),
+            )?;
+            count += 1;
+        }
+        profile::add_rows(count);
+        Ok(count)
+    })
+}
+
+// Path that stands for standard input
+pub const STDIN_PATH: &str = "-";
+
+// Open a file path for reading, or standard input for "-"
+pub(crate) fn open_input(file_path: &str) -> Result<Box<dyn Read>, Box<dyn Error>> {
+    if file_path == STDIN_PATH {
+        Ok(Box::new(io::stdin().lock()))
+    } else {
+        Ok(Box::new(File::open(file_path)?))
+    }
+}
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+// Skip a UTF-8 byte order mark at the start of the input
+fn skip_utf8_bom(reader: &mut impl BufRead) -> io::Result<()> {
+    if reader.fill_buf()?.starts_with(UTF8_BOM) {
+        reader.consume(UTF8_BOM.len());
+    }
+    Ok(())
+}
+
+// Decompress gzip or zip input and optionally skip a leading byte order mark
+pub(crate) fn prepare_input<'a>(
+    reader: impl Read + 'a,
+    strip_bom: bool,
+) -> Result<BufReader<Box<dyn Read + 'a>>, Box<dyn Error>> {
+    let mut reader = BufReader::new(decompressed(reader)?);
+    if strip_bom {
+        skip_utf8_bom(&mut reader)?;
+    }
+    Ok(reader)
+}
+
+// Puts a byte order mark in front of the first read. The csv parser always drops
+// a leading one, so this makes it drop ours and keep the input's.
+pub(crate) struct KeepBom<R> {
+    inner: R,
+    pending: bool,
+}
+
+impl<R: Read> Read for KeepBom<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // The mark must arrive with data, since an empty chunk ends the input
+        if !self.pending || buf.len() <= UTF8_BOM.len() {
+            return self.inner.read(buf);
+        }
+        self.pending = false;
+        buf[..UTF8_BOM.len()].copy_from_slice(UTF8_BOM);
+        let n = self.inner.read(&mut buf[UTF8_BOM.len()..])?;
+        Ok(UTF8_BOM.len() + n)
+    }
+}
+
+// CSV reader configured for a dialect
+pub(crate) fn csv_reader<R: Read>(reader: R, csv_options: &CsvOptions) -> csv::Reader<KeepBom<R>> {
+    ReaderBuilder::new()
+        .has_headers(csv_options.has_headers)
+        .delimiter(csv_options.delimiter)
+        .quote(csv_options.quote)
+        .comment(csv_options.comment)
+        .trim(if csv_options.trim {
+            Trim::All
+        } else {
+            Trim::None
+        })
+        .from_reader(KeepBom {
+            inner: reader,
+            pending: !csv_options.strip_bom,
+        })
+}
+
+// Load data from any reader to a table, returning the number of records.
+// Gzip and zip input is decompressed first.
+pub fn load_data_from_reader(
+    conn: &Connection,
+    table_name: &str,
+    reader: impl Read,
+    options: &LoadOptions,
+) -> Result<usize, Box<dyn Error>> {
+    // JSON cannot start with a byte order mark, so only CSV may keep it
+    let strip_bom = options.format != LoadFormat::Csv || options.csv.strip_bom;
+    let reader = prepare_input(reader, strip_bom)?;
+
+    match options.format {
+        LoadFormat::Csv => {
+            let csv_options = &options.csv;
+            let mut rdr = csv_reader(reader, csv_options);
+            let records = rdr
+                .records()
+                .map(|result| LoadRecord::from_csv(&result?, csv_options.decimal_separator));
+            insert_records(conn, table_name, records)
+        }
+        LoadFormat::Json => {
+            let value: Value = serde_json::from_reader(reader)?;
+            let array = value.as_array().ok_or("expected a JSON array of objects")?;
+            insert_records(conn, table_name, array.iter().map(LoadRecord::from_json))
+        }
+        LoadFormat::Ndjson => {
+            let records = reader
+                .lines()
+                .filter(|line| !matches!(line, Ok(l) if l.trim().is_empty()))
+                .map(|line| LoadRecord::from_json(&serde_json::from_str(&line?)?));
+            insert_records(conn, table_name, records)
+        }
+    }
+}
+
+// Load data from a file path, or "-" for standard input, to a table
+pub fn load_data(
+    conn: &Connection,
+    table_name: &str,
+    file_path: &str,
+    options: &LoadOptions,
+) -> Result<(), Box<dyn Error>> {
+    //Box<dyn Error> is a trait object that can represent any error type
+    let reader = open_input(file_path)?;
+    load_data_from_reader(conn, table_name, reader, options)?;
+
+    let source = if file_path == STDIN_PATH {
+        "stdin"
+    } else {
+        file_path
+    };
+    println!(
+        "Data loaded successfully from '{}' into table '{}'.",
+        source, table_name
+    );
+    Ok(())
+}
+
+// Load data from a file path with the given format and default options
+fn load_data_as(
+    conn: &Connection,
+    table_name: &str,
+    file_path: &str,
+    format: LoadFormat,
+) -> Result<(), Box<dyn Error>> {
+    let options = LoadOptions {
+        format,
+        ..LoadOptions::default()
+    };
+    load_data(conn, table_name, file_path, &options)
+}
+
+// Load data from a CSV file path to a table
+pub fn load_data_from_csv(
+    conn: &Connection,
+    table_name: &str,
+    file_path: &str,
+) -> Result<(), Box<dyn Error>> {
+    load_data_as(conn, table_name, file_path, LoadFormat::Csv)
+}
+
+// Load data from a file holding a JSON array of objects
+pub fn load_data_from_json(
+    conn: &Connection,
+    table_name: &str,
+    file_path: &str,
+) -> Result<(), Box<dyn Error>> {
+    load_data_as(conn, table_name, file_path, LoadFormat::Json)
+}
+
+// Load data from a file with one JSON object per line
+pub fn load_data_from_ndjson(
+    conn: &Connection,
+    table_name: &str,
+    file_path: &str,
+) -> Result<(), Box<dyn Error>> {
+    load_data_as(conn, table_name, file_path, LoadFormat::Ndjson)
+}
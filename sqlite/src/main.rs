@@ -3,15 +3,45 @@
 // }
 //this will be the CLI portion of the project where we accept
 //user defined arguments and call lib.rs logic to handle them
-use clap::{Parser, Subcommand};
-use rusqlite::{Connection, Result};
-use sqlite::UpdateFields;
+mod repl;
+
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use repl::{execute_line, report_error, run_repl};
+use rusqlite::types::Value;
+use rusqlite::{Connection, DatabaseName, OpenFlags, Result};
+use sqlite::audit::{history_exec, set_audit_user, undo_exec};
+use sqlite::backup::{backup_exec, restore_backup_exec, DEFAULT_PAGES_PER_STEP};
+use sqlite::compare::{compare_exec, PythonSide};
+use sqlite::config::{config, config_show_exec, load_config, set_config, Config, Origin};
+use sqlite::connection::{
+    open_connection, open_connection_with_flags, read_connection_settings, set_connection_settings,
+    ConnectionSettings, JournalMode, Synchronous,
+};
+use sqlite::export::{export_exec, ExportFormat};
+use sqlite::generate::{generate_exec, DEFAULT_SEED};
+use sqlite::generic::{load_generic_csv, DEFAULT_SAMPLE_ROWS};
+use sqlite::pipeline::pipeline_exec;
+use sqlite::profile::{
+    read_profiles_csv, write_profile_exec, write_profiles, ProfileFormat, Profiler,
+    DEFAULT_SAMPLE_INTERVAL,
+};
+use sqlite::report::{render_report, summarize, Environment};
+use sqlite::schema::{count_exec, describe_exec, tables_exec};
+use sqlite::script::{read_script, OnError};
+use sqlite::sql::{parse_named_param, parse_param_value, sql_exec, OutputFormat, QueryParams};
 use sqlite::{
-    create_exec, create_table, delete_exec, drop_table, extract, load_data_from_csv, read_exec,
-    update_exec,
+    create_exec, create_table, delete_exec, drop_table, extract, extract_file_path, extract_from,
+    load_data, purge_exec, read_exec_with_options, restore_exec, soft_delete_exec, update_exec,
 };
-use std::time::Instant;
-use sysinfo::{ProcessExt, System, SystemExt};
+use sqlite::{
+    parse_csv_byte, parse_decimal_separator, CsvOptions, LoadFormat, LoadOptions, ReadOptions,
+    UpdateFields, FIFA_FILE_PATH, FIFA_URL,
+};
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::time::{Duration, Instant};
 
 //Here we define a struct (or object) to hold our CLI arguments
 //for #[STUFF HERE] syntax, these are called attributes.
@@ -24,6 +54,84 @@ use sysinfo::{ProcessExt, System, SystemExt};
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    ///User label recorded in the audit trail (defaults to $USER)
+    #[arg(long, global = true)]
+    user: Option<String>,
+    ///Database file to open; defaults to the config file's database, then my_database.db
+    #[arg(long, global = true, value_name = "PATH")]
+    database: Option<String>,
+    #[command(flatten)]
+    profile: ProfileArgs,
+    #[command(flatten)]
+    connection: ConnectionArgs,
+}
+
+//Profiling flags, accepted by every command
+#[derive(Debug, Args)]
+struct ProfileArgs {
+    ///Measure wall time, CPU time, memory and rows processed, written as json or csv
+    /// "sqlite -q table1 --profile" or "sqlite load table1 data.csv --profile=csv"
+    #[arg(
+        long,
+        global = true,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "json"
+    )]
+    profile: Option<ProfileFormat>,
+    ///Append profiles to this file instead of writing them to stderr
+    #[arg(long, global = true, requires = "profile")]
+    profile_output: Option<String>,
+    ///Milliseconds between memory samples taken while the command runs
+    #[arg(long, global = true, value_name = "MS", default_value_t = DEFAULT_SAMPLE_INTERVAL.as_millis() as u64, requires = "profile", value_parser = clap::value_parser!(u64).range(1..))]
+    profile_interval: u64,
+}
+
+//Connection tuning flags, accepted by every command and applied to every connection
+#[derive(Debug, Args)]
+struct ConnectionArgs {
+    ///PRAGMA journal_mode: delete, truncate, persist, memory, wal or off
+    /// "sqlite load table1 data.csv --journal-mode wal --synchronous normal"
+    #[arg(long, global = true, value_name = "MODE")]
+    journal_mode: Option<JournalMode>,
+    ///PRAGMA synchronous: off, normal, full or extra
+    #[arg(long, global = true, value_name = "LEVEL")]
+    synchronous: Option<Synchronous>,
+    ///PRAGMA cache_size: pages when positive, KiB when negative
+    #[arg(long, global = true, value_name = "N", allow_negative_numbers = true)]
+    cache_size: Option<i64>,
+    ///PRAGMA mmap_size in bytes; 0 turns memory mapping off
+    #[arg(long, global = true, value_name = "BYTES")]
+    mmap_size: Option<i64>,
+    ///Milliseconds to wait for a locked database before failing [default: 5000]
+    #[arg(long, global = true, value_name = "MS")]
+    busy_timeout: Option<u64>,
+    ///TOML file of connection settings, e.g. journal_mode = "wal"; overrides the config
+    ///files' [connection] table, and flags take precedence over it
+    #[arg(long, global = true, value_name = "PATH")]
+    connection_config: Option<String>,
+}
+
+impl ConnectionArgs {
+    //Layer the settings file, then the flags, over `config`
+    fn apply(&self, config: &mut Config) -> Result<(), Box<dyn Error>> {
+        if let Some(path) = &self.connection_config {
+            let settings = read_connection_settings(path)?;
+            config.apply_connection(&settings, &Origin::File(path.into()));
+        }
+        config.apply_connection(
+            &ConnectionSettings {
+                journal_mode: self.journal_mode,
+                synchronous: self.synchronous,
+                cache_size: self.cache_size,
+                mmap_size: self.mmap_size,
+                busy_timeout: self.busy_timeout,
+            },
+            &Origin::Flag,
+        );
+        Ok(())
+    }
 }
 
 //An enum is a type in rust that can have multiple exauhstive and mutually exclusive options
@@ -33,106 +141,608 @@ struct Cli {
 //By separating out the commands as enum types we can easily match what the user is
 //trying to do in main
 enum Commands {
-    ///Extract a url to a file path
+    ///Extract a url to a file path, decompressing .gz and .zip resources
+    /// "sqlite -e" or "sqlite -e --url https://example.com/snapshot.csv.gz"
     #[command(alias = "e", short_flag = 'e')]
-    Extract {},
+    Extract {
+        ///Defaults to the config file's source_url, then the FIFA audience dataset
+        #[arg(long)]
+        url: Option<String>,
+        ///Defaults to the url's file name under data/
+        #[arg(long)]
+        output: Option<String>,
+    },
     ///Pass a table name to create a table
     /// "sqlite -c table1"
     #[command(alias = "c", short_flag = 'c')]
-    Create { table_name: String },
-    ///Pass a table name and a file path to load data from csv
-    /// "sqlite -l table1 data/fifa_countries_audience.csv"
-    #[command(alias = "l", short_flag = 'l')]
+    Create {
+        ///Defaults to the config file's table
+        table_name: Option<String>,
+    },
+    ///Pass a table name and a file path, or - for stdin, to load data from csv, json or ndjson
+    /// "sqlite -l table1 data/fifa_countries_audience.csv" or "cat data.ndjson | sqlite -l table1 - --format ndjson"
+    #[command(alias = "l", short_flag = 'l', allow_missing_positional = true)]
     Load {
-        table_name: String,
+        ///Defaults to the config file's table
+        table_name: Option<String>,
         file_path: String,
+        ///csv, json or ndjson; defaults to the file extension, then csv
+        #[arg(long)]
+        format: Option<LoadFormat>,
+        #[command(flatten)]
+        csv: CsvArgs,
+        ///Infer the table's columns and types from the CSV header instead of
+        ///using the FIFA schema, creating the table if needed
+        #[arg(long)]
+        generic: bool,
+        ///Rows sampled to infer column types in --generic mode
+        #[arg(long, default_value_t = DEFAULT_SAMPLE_ROWS, requires = "generic")]
+        sample_rows: usize,
     },
     ///Pass a table name to read data
     /// "sqlite -q table1"
     #[command(alias = "q", short_flag = 'q')]
-    Query { table_name: String },
+    Query {
+        ///Defaults to the config file's table
+        table_name: Option<String>,
+        ///Also show soft-deleted records
+        #[arg(long)]
+        include_deleted: bool,
+        ///Rows to print; reading stops there. Defaults to 10
+        #[arg(long)]
+        limit: Option<usize>,
+    },
     ///Pass a table name to drop
     /// "sqlite -d table1"
     #[command(alias = "d", short_flag = 'd')]
-    Drop { table_name: String },
+    Drop {
+        ///Defaults to the config file's table
+        table_name: Option<String>,
+    },
     ///Pass a new record to insert
     /// "sqlite -i table1 TestCountry1 TestConfederation2 0.1 0.2 0.3"
     #[command(alias = "i", short_flag = 'i')]
     Insert {
-        table_name: String,
-        country: String,
-        confederation: String,
-        population_share: f64,
-        tv_audience_share: f64,
-        gdp_weighted_share: f64,
-    },
-    ///Pass a new record to update
-    /// "sqlite -u table1 192 TestCountry1 TestConfederation2 1.1 2.2 3.3"
-    #[command(alias = "u", short_flag = 'u')]
+        #[command(flatten)]
+        record: InsertArgs,
+    },
+    ///Update a record by id, changing only the fields passed as flags
+    /// "sqlite -u table1 192 --tv-audience-share 2.2 --clear confederation"
+    #[command(alias = "u", short_flag = 'u', allow_missing_positional = true)]
     Update {
-        table_name: String,
+        ///Defaults to the config file's table
+        table_name: Option<String>,
         id: i32,
+        #[arg(long = "country")]
         new_country: Option<String>,
+        #[arg(long = "confederation")]
         new_confederation: Option<String>,
+        #[arg(long = "population-share")]
         new_population_share: Option<f64>,
+        #[arg(long = "tv-audience-share")]
         new_tv_audience_share: Option<f64>,
+        #[arg(long = "gdp-weighted-share")]
         new_gdp_weighted_share: Option<f64>,
+        ///Columns to set to NULL, e.g. "--clear country,population-share"
+        #[arg(long, value_enum, value_delimiter = ',')]
+        clear: Vec<UpdateColumn>,
     },
     ///Delete a record by id
-    /// "sqlite -x table1 192"
-    #[command(alias = "x", short_flag = 'x')]
-    Delete { table_name: String, id: i32 },
+    /// "sqlite -x table1 192" or "sqlite -x table1 192 --soft"
+    #[command(alias = "x", short_flag = 'x', allow_missing_positional = true)]
+    Delete {
+        ///Defaults to the config file's table
+        table_name: Option<String>,
+        id: i32,
+        ///Mark the record as deleted so it can be restored later
+        #[arg(long)]
+        soft: bool,
+    },
+    ///Restore a soft-deleted record
+    /// "sqlite restore table1 192"
+    #[command(allow_missing_positional = true)]
+    Restore {
+        ///Defaults to the config file's table
+        table_name: Option<String>,
+        id: i32,
+    },
+    ///Permanently remove soft-deleted records, all of them or one by id
+    /// "sqlite purge table1" or "sqlite purge table1 192"
+    Purge {
+        ///Defaults to the config file's table; a lone number is taken as the id
+        table_name: Option<String>,
+        id: Option<i32>,
+    },
+    ///Back up the database to a file while it stays in use
+    /// "sqlite backup backups/my_database.db"
+    Backup {
+        dest_path: String,
+        #[arg(long, default_value_t = DEFAULT_PAGES_PER_STEP)]
+        pages_per_step: i32,
+    },
+    ///Export a table as csv, json, ndjson or sql, to a file or stdout
+    /// "sqlite export table1 table1.csv" or "sqlite export table1 --format ndjson"
+    Export {
+        ///Defaults to the config file's table; a lone value with a '.' or path
+        ///separator in it is taken as the output path
+        table_name: Option<String>,
+        output_path: Option<String>,
+        ///csv, json, ndjson or sql; defaults to the output file extension, then csv
+        #[arg(long)]
+        format: Option<ExportFormat>,
+    },
+    ///Replace the database with the contents of a backup file
+    /// "sqlite restore-backup backups/my_database.db"
+    RestoreBackup {
+        src_path: String,
+        #[arg(long, default_value_t = DEFAULT_PAGES_PER_STEP)]
+        pages_per_step: i32,
+    },
+    ///Show the audited change history of a record
+    /// "sqlite history table1 192"
+    #[command(allow_missing_positional = true)]
+    History {
+        ///Defaults to the config file's table
+        table_name: Option<String>,
+        id: i32,
+    },
+    ///Revert the most recent insert, update, delete or load, or the given op id
+    /// "sqlite undo" or "sqlite undo 12"
+    Undo { op_id: Option<i64> },
+    ///Run a SQL query with bound parameters; read-only unless --write is passed
+    /// "sqlite sql 'SELECT * FROM table1 WHERE confederation = ?1 LIMIT ?2' CONCACAF 5"
+    /// or "sqlite sql 'SELECT * FROM table1 WHERE id = :id' --param id=192 --format json"
+    Sql {
+        query: String,
+        ///Values for ?, ?1, ?2, ... in order; numbers and NULL are typed, the rest is text
+        #[arg(value_parser = parse_param_value_arg)]
+        params: Vec<Value>,
+        ///Named parameter as name=value, for :name, @name or $name
+        #[arg(long = "param", value_parser = parse_named_param)]
+        named_params: Vec<(String, Value)>,
+        ///table, csv, json or ndjson; defaults to the config file's output_format, then table
+        #[arg(long)]
+        format: Option<OutputFormat>,
+        ///Open the database read-write so the query may change it
+        #[arg(long)]
+        write: bool,
+    },
+    ///Start an interactive shell on one connection that runs these commands
+    ///and SQL statements ending in ';', with history, completion and timing
+    /// "sqlite repl"
+    #[command(alias = "shell")]
+    Repl,
+    ///Run the commands in a script file on one connection: one command or SQL
+    ///statement per line, or a "commands" list in a .toml file
+    /// "sqlite run setup.txt" or "sqlite run pipeline.toml --transaction --on-error continue"
+    Run {
+        script_path: String,
+        ///Run all commands in one transaction, rolled back if the script stops on an error
+        #[arg(long)]
+        transaction: bool,
+        ///stop or continue; defaults to the script's on_error setting, then stop
+        #[arg(long)]
+        on_error: Option<OnError>,
+    },
+    ///Run a pipeline file: extract, create, load, validate and transform a table
+    /// "sqlite pipeline run pipelines/fifa.toml"
+    Pipeline {
+        #[command(subcommand)]
+        action: PipelineAction,
+    },
+    ///Run a benchmark workload on a scratch database and write a Markdown report of its timing and memory
+    /// "sqlite report" or "sqlite report --workload bench.txt --iterations 5 --baseline last.csv"
+    Report {
+        ///Script of commands to measure, as for `run`; defaults to a create/load/query/drop workload
+        #[arg(long)]
+        workload: Option<String>,
+        ///Times to run the whole workload
+        #[arg(long, default_value_t = 1)]
+        iterations: usize,
+        #[arg(long, default_value = "performance_report.md")]
+        output: String,
+        ///Profiles CSV of an earlier run to compare against
+        #[arg(long)]
+        baseline: Option<String>,
+        ///Also write this run's profiles as CSV, for use as a later baseline
+        #[arg(long)]
+        save_profiles: Option<String>,
+    },
+    ///Load a CSV with both this crate and the Python mylib loader, then compare
+    ///the resulting tables row by row and by aggregates, with the time each took
+    /// "sqlite compare" or "sqlite compare --no-python --python-db ../FifaDB.db"
+    Compare {
+        #[arg(long, default_value = FIFA_FILE_PATH)]
+        csv: String,
+        ///Python interpreter that runs mylib.transform_load
+        #[arg(long, default_value = "python3")]
+        python: String,
+        ///Directory holding the mylib package; the Python loader writes FifaDB.db there
+        #[arg(long, default_value = "..")]
+        python_dir: String,
+        ///Python-produced database to compare against; defaults to FifaDB.db in --python-dir
+        #[arg(long)]
+        python_db: Option<String>,
+        ///Compare against the existing Python database instead of running the loader
+        #[arg(long)]
+        no_python: bool,
+    },
+    ///Write a synthetic country-audience CSV of any size for benchmarking
+    /// "sqlite generate data/fifa_1m.csv --rows 1000000 --seed 7"
+    Generate {
+        file_path: String,
+        #[arg(long, default_value_t = 1_000_000)]
+        rows: usize,
+        ///The same seed always produces the same file
+        #[arg(long, default_value_t = DEFAULT_SEED)]
+        seed: u64,
+    },
+    ///List the tables and views in the database
+    /// "sqlite tables"
+    Tables,
+    ///Show a table's columns, types, constraints, indexes and row count
+    /// "sqlite describe table1"
+    Describe {
+        ///Defaults to the config file's table
+        table_name: Option<String>,
+    },
+    ///Count the rows of a table, leaving out soft-deleted records
+    /// "sqlite count table1" or "sqlite count table1 --include-deleted"
+    Count {
+        ///Defaults to the config file's table
+        table_name: Option<String>,
+        #[arg(long)]
+        include_deleted: bool,
+    },
+    ///Inspect the configuration read from ~/.config/sqlite/config.toml and .sqlite.toml
+    /// "sqlite config show"
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigAction {
+    ///List every effective setting and where it was set
+    Show,
+}
+
+//Workload `report` runs when no workload file is given
+const DEFAULT_WORKLOAD: [&str; 7] = [
+    "create report_bench",
+    "load report_bench data/fifa_countries_audience.csv",
+    "query report_bench",
+    "insert report_bench TestCountry TestConfederation 0.1 0.2 0.3",
+    "update report_bench 192 --tv-audience-share 2.2",
+    "delete report_bench 192",
+    "drop report_bench",
+];
+
+#[derive(Debug, Subcommand)]
+enum PipelineAction {
+    ///Run every step of a .toml pipeline, reporting the status of each
+    Run { pipeline_path: String },
+}
+
+//clap value parsers must be fallible
+fn parse_param_value_arg(s: &str) -> Result<Value, String> {
+    Ok(parse_param_value(s))
+}
+
+//CSV dialect flags for reading files that are not plain comma-separated
+#[derive(Debug, Args)]
+struct CsvArgs {
+    ///Field delimiter: one character, or tab, comma, semicolon, pipe or space
+    #[arg(long, default_value = ",", value_parser = parse_csv_byte)]
+    delimiter: u8,
+    ///Treat the first line as data instead of a header
+    #[arg(long)]
+    no_header: bool,
+    ///Quote character
+    #[arg(long, default_value = "\"", value_parser = parse_csv_byte)]
+    quote: u8,
+    ///Skip lines starting with this character
+    #[arg(long, value_parser = parse_csv_byte)]
+    comment: Option<u8>,
+    ///Trim whitespace around fields
+    #[arg(long)]
+    trim: bool,
+    ///Decimal separator in the share columns, "." or ","
+    #[arg(long, default_value = ".", value_parser = parse_decimal_separator)]
+    decimal: char,
+    ///Keep a UTF-8 byte order mark at the start of the file as part of the first field
+    #[arg(long)]
+    keep_bom: bool,
+}
+
+impl Default for CsvArgs {
+    fn default() -> Self {
+        let defaults = CsvOptions::default();
+        CsvArgs {
+            delimiter: defaults.delimiter,
+            no_header: !defaults.has_headers,
+            quote: defaults.quote,
+            comment: defaults.comment,
+            trim: defaults.trim,
+            decimal: defaults.decimal_separator,
+            keep_bom: !defaults.strip_bom,
+        }
+    }
+}
+
+impl TryFrom<CsvArgs> for CsvOptions {
+    type Error = clap::Error;
+
+    fn try_from(args: CsvArgs) -> Result<Self, Self::Error> {
+        let options = CsvOptions {
+            delimiter: args.delimiter,
+            has_headers: !args.no_header,
+            quote: args.quote,
+            comment: args.comment,
+            trim: args.trim,
+            decimal_separator: args.decimal,
+            strip_bom: !args.keep_bom,
+        };
+        options
+            .validate()
+            .map_err(|e| Cli::command().error(ErrorKind::ArgumentConflict, e))?;
+        Ok(options)
+    }
+}
+
+//Positional arguments of insert. Clap can only leave out an optional positional
+//when it is second to last, so the optional leading table is told apart here by
+//counting the values instead of by a derive.
+#[derive(Debug, Clone, PartialEq)]
+struct InsertArgs {
+    table_name: Option<String>,
+    country: String,
+    confederation: String,
+    population_share: f64,
+    tv_audience_share: f64,
+    gdp_weighted_share: f64,
+}
+
+const INSERT_VALUES: &str = "values";
+
+impl Args for InsertArgs {
+    fn augment_args(cmd: clap::Command) -> clap::Command {
+        cmd.arg(
+            clap::Arg::new(INSERT_VALUES)
+                .help("The record's five values, after the table name unless it is left to default to the config file's table")
+                .num_args(5..=6)
+                .required(true)
+                .value_names([
+                    "TABLE_NAME",
+                    "COUNTRY",
+                    "CONFEDERATION",
+                    "POPULATION_SHARE",
+                    "TV_AUDIENCE_SHARE",
+                    "GDP_WEIGHTED_SHARE",
+                ]),
+        )
+    }
+
+    fn augment_args_for_update(cmd: clap::Command) -> clap::Command {
+        Self::augment_args(cmd)
+    }
+}
+
+impl FromArgMatches for InsertArgs {
+    fn from_arg_matches(matches: &clap::ArgMatches) -> Result<Self, clap::Error> {
+        let values: Vec<&String> = matches
+            .get_many::<String>(INSERT_VALUES)
+            .ok_or_else(|| clap::Error::new(ErrorKind::MissingRequiredArgument))?
+            .collect();
+        //Clap has checked there are five or six values; six start with the table
+        let (table_name, record) = match values.split_at(values.len() - 5) {
+            ([table_name], record) => (Some(table_name.to_string()), record),
+            (_, record) => (None, record),
+        };
+        let share = |i: usize, column: &str| -> Result<f64, clap::Error> {
+            record[i].parse().map_err(|e| {
+                clap::Error::raw(
+                    ErrorKind::ValueValidation,
+                    format!("invalid {} '{}': {}", column, record[i], e),
+                )
+            })
+        };
+        Ok(InsertArgs {
+            table_name,
+            country: record[0].to_string(),
+            confederation: record[1].to_string(),
+            population_share: share(2, "population_share")?,
+            tv_audience_share: share(3, "tv_audience_share")?,
+            gdp_weighted_share: share(4, "gdp_weighted_share")?,
+        })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &clap::ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+//Columns of the country table that `update --clear` can set to NULL
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum UpdateColumn {
+    Country,
+    Confederation,
+    PopulationShare,
+    TvAudienceShare,
+    GdpWeightedShare,
+}
+
+//Combine a flag value and the --clear list into the UpdateFields tri-state
+fn update_value<T>(
+    value: Option<T>,
+    column: UpdateColumn,
+    clear: &[UpdateColumn],
+) -> Result<Option<Option<T>>, clap::Error> {
+    if clear.contains(&column) {
+        if value.is_some() {
+            return Err(Cli::command().error(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "cannot both set and clear '{}'",
+                    column.to_possible_value().unwrap().get_name()
+                ),
+            ));
+        }
+        Ok(Some(None))
+    } else {
+        Ok(value.map(Some))
+    }
 }
 
-fn cli_main(command: Commands) -> Result<()> {
+//Prefix a library error with what the command was doing
+fn context<E: std::fmt::Display>(action: &'static str) -> impl FnOnce(E) -> String {
+    move |e| format!("{}: {}", action, e)
+}
+
+fn cli_main(command: Commands) -> Result<(), Box<dyn Error>> {
     //Here we parse the CLI arguments and store them in the args object
     // let args = Cli::parse();
-    //generate connection
-    let conn = Connection::open("my_database.db")?;
+    //generate connection, tuned by the connection settings. `sql` without --write
+    //opens the database read-only, so it never creates a missing file.
+    let database = config().database.value;
+    let conn = match &command {
+        Commands::Sql { write: false, .. } => {
+            open_connection_with_flags(&database, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        }
+        _ => open_connection(&database),
+    };
+    conn.and_then(|mut conn| match command {
+        Commands::Repl => run_repl(&mut conn),
+        command => run_command(&mut conn, command),
+    })
+}
+
+//Print an error and end the process with a failing exit code. Usage errors
+//print like clap's own.
+fn exit_with_error(e: Box<dyn Error>) -> ! {
+    if let Some(e) = e.downcast_ref::<clap::Error>() {
+        e.exit();
+    }
+    eprintln!("Error: {}", e);
+    std::process::exit(1);
+}
+
+//The named table, else the config file's default table
+fn table_or_default(table_name: Option<String>) -> Result<String, Box<dyn Error>> {
+    table_name
+        .or(config().table.value)
+        .ok_or_else(|| "no table given and no default table in the config file".into())
+}
+
+//For commands whose table and next argument are both optional, clap puts a lone
+//value in the table's place; move it to the next argument when `parse` accepts it
+fn shift_lone_value<T>(
+    table_name: Option<String>,
+    next: Option<T>,
+    parse: impl Fn(&str) -> Option<T>,
+) -> (Option<String>, Option<T>) {
+    match (table_name, next) {
+        (Some(value), None) => match parse(&value) {
+            Some(next) => (None, Some(next)),
+            None => (Some(value), None),
+        },
+        other => other,
+    }
+}
 
+//Run one command on an open connection; shared by the CLI and the REPL
+fn run_command(conn: &mut Connection, command: Commands) -> Result<(), Box<dyn Error>> {
     //Here we can match the behavior on the subcommand and call our lib logic
     match command {
-        Commands::Extract {} => {
+        Commands::Extract { url, output } => {
             println!("Extract a url to a file path");
-            extract().expect("Failed to extract");
+            let default_url = config().source_url.value;
+            match (url, output) {
+                (None, None) if default_url == FIFA_URL => extract(),
+                (url, output) => {
+                    let url = url.unwrap_or(default_url);
+                    let output = output.unwrap_or_else(|| extract_file_path(&url));
+                    extract_from(&url, &output)
+                }
+            }
+            .map_err(context("Failed to extract"))?;
         }
         Commands::Create { table_name } => {
+            let table_name = table_or_default(table_name)?;
             println!("Creating Table {}", table_name);
-            create_table(&conn, &table_name).expect("Failed to create table");
+            create_table(conn, &table_name).map_err(context("Failed to create table"))?;
         }
-        Commands::Query { table_name } => {
+        Commands::Query {
+            table_name,
+            include_deleted,
+            limit,
+        } => {
+            let table_name = table_or_default(table_name)?;
             println!("Read Table: {}", table_name);
-            read_exec(&conn, &table_name).expect("Failed to execute query");
+            let options = ReadOptions {
+                include_deleted,
+                limit,
+            };
+            read_exec_with_options(conn, &table_name, &options)
+                .map_err(context("Failed to execute query"))?;
         }
         Commands::Drop { table_name } => {
+            let table_name = table_or_default(table_name)?;
             println!("Deleting: {}", table_name);
-            drop_table(&conn, &table_name).expect("Failed to drop table");
+            drop_table(conn, &table_name).map_err(context("Failed to drop table"))?;
         }
         Commands::Load {
             table_name,
             file_path,
+            format,
+            csv,
+            generic,
+            sample_rows,
         } => {
+            let table_name = table_or_default(table_name)?;
+            let csv = CsvOptions::try_from(csv)?;
+            let format = format
+                .or_else(|| LoadFormat::from_path(&file_path))
+                .unwrap_or(LoadFormat::Csv);
             println!(
-                "Loading data into table '{}' from '{}'",
-                table_name, file_path
+                "Loading data into table '{}' from '{}' as {:?}",
+                table_name, file_path, format
             );
-            load_data_from_csv(&conn, &table_name, &file_path)
-                .expect("Failed to load data from csv");
+            if generic {
+                if format != LoadFormat::Csv {
+                    return Err(Cli::command()
+                        .error(
+                            ErrorKind::ArgumentConflict,
+                            "--generic only supports csv input",
+                        )
+                        .into());
+                }
+                load_generic_csv(conn, &table_name, &file_path, &csv, sample_rows)
+                    .map_err(context("Failed to load data"))?;
+            } else {
+                let options = LoadOptions { format, csv };
+                load_data(conn, &table_name, &file_path, &options)
+                    .map_err(context("Failed to load data"))?;
+            }
         }
-        Commands::Insert {
-            table_name,
-            country,
-            confederation,
-            population_share,
-            tv_audience_share,
-            gdp_weighted_share,
-        } => {
+        Commands::Insert { record } => {
+            let InsertArgs {
+                table_name,
+                country,
+                confederation,
+                population_share,
+                tv_audience_share,
+                gdp_weighted_share,
+            } = record;
+            let table_name = table_or_default(table_name)?;
             println!(
                 "Insert record in table '{}' with country {}, confederation {}, population_share {}, tv_audience_share {}, gdp_weighted_share {}",
                 table_name, country, confederation, population_share, tv_audience_share, gdp_weighted_share
             );
             create_exec(
-                &conn,
+                conn,
                 &table_name,
                 &country,
                 &confederation,
@@ -140,7 +750,7 @@ fn cli_main(command: Commands) -> Result<()> {
                 tv_audience_share,
                 gdp_weighted_share,
             )
-            .expect("Failed to insert record");
+            .map_err(context("Failed to insert record"))?;
         }
         Commands::Update {
             table_name,
@@ -150,46 +760,334 @@ fn cli_main(command: Commands) -> Result<()> {
             new_population_share,
             new_tv_audience_share,
             new_gdp_weighted_share,
+            clear,
         } => {
+            let table_name = table_or_default(table_name)?;
             println!("Updating record in table '{}' with ID {}", table_name, id);
             let fields = UpdateFields {
-                new_country: new_country.as_deref(),
-                new_confederation: new_confederation.as_deref(),
-                new_population_share,
-                new_tv_audience_share,
-                new_gdp_weighted_share,
+                new_country: update_value(new_country.as_deref(), UpdateColumn::Country, &clear)?,
+                new_confederation: update_value(
+                    new_confederation.as_deref(),
+                    UpdateColumn::Confederation,
+                    &clear,
+                )?,
+                new_population_share: update_value(
+                    new_population_share,
+                    UpdateColumn::PopulationShare,
+                    &clear,
+                )?,
+                new_tv_audience_share: update_value(
+                    new_tv_audience_share,
+                    UpdateColumn::TvAudienceShare,
+                    &clear,
+                )?,
+                new_gdp_weighted_share: update_value(
+                    new_gdp_weighted_share,
+                    UpdateColumn::GdpWeightedShare,
+                    &clear,
+                )?,
             };
-            update_exec(&conn, &table_name, id, fields).expect("Failed to update record");
+            update_exec(conn, &table_name, id, fields)
+                .map_err(context("Failed to update record"))?;
         }
-        Commands::Delete { table_name, id } => {
+        Commands::Delete {
+            table_name,
+            id,
+            soft,
+        } => {
+            let table_name = table_or_default(table_name)?;
             println!("Delete record in table '{}' with ID {}", table_name, id);
-            delete_exec(&conn, &table_name, id).expect("Failed to delete record");
+            if soft {
+                soft_delete_exec(conn, &table_name, id)
+                    .map_err(context("Failed to delete record"))?;
+            } else {
+                delete_exec(conn, &table_name, id).map_err(context("Failed to delete record"))?;
+            }
+        }
+        Commands::Restore { table_name, id } => {
+            let table_name = table_or_default(table_name)?;
+            println!("Restore record in table '{}' with ID {}", table_name, id);
+            restore_exec(conn, &table_name, id).map_err(context("Failed to restore record"))?;
+        }
+        Commands::Purge { table_name, id } => {
+            //Table names cannot be bare numbers, so a lone number is the id
+            let (table_name, id) = shift_lone_value(table_name, id, |value| value.parse().ok());
+            let table_name = table_or_default(table_name)?;
+            println!("Purge soft-deleted records in table '{}'", table_name);
+            purge_exec(conn, &table_name, id).map_err(context("Failed to purge records"))?;
+        }
+        Commands::Backup {
+            dest_path,
+            pages_per_step,
+        } => {
+            println!("Backing up database to '{}'", dest_path);
+            backup_exec(conn, &dest_path, pages_per_step)
+                .map_err(context("Failed to back up database"))?;
+        }
+        Commands::Export {
+            table_name,
+            output_path,
+            format,
+        } => {
+            //Table names cannot hold '.' or path separators, so such a lone value is the path
+            let (table_name, output_path) = shift_lone_value(table_name, output_path, |value| {
+                value
+                    .contains(['.', '/', std::path::MAIN_SEPARATOR])
+                    .then(|| value.to_string())
+            });
+            let table_name = table_or_default(table_name)?;
+            let format = format
+                .or_else(|| output_path.as_deref().and_then(ExportFormat::from_path))
+                .unwrap_or(ExportFormat::Csv);
+            // stderr, since the export itself may be going to stdout
+            eprintln!("Exporting table '{}' as {:?}", table_name, format);
+            export_exec(conn, &table_name, format, output_path.as_deref())
+                .map_err(context("Failed to export table"))?;
+        }
+        Commands::RestoreBackup {
+            src_path,
+            pages_per_step,
+        } => {
+            println!("Restoring database from '{}'", src_path);
+            restore_backup_exec(conn, &src_path, pages_per_step)
+                .map_err(context("Failed to restore database"))?;
+        }
+        Commands::History { table_name, id } => {
+            let table_name = table_or_default(table_name)?;
+            println!("History of record in table '{}' with ID {}", table_name, id);
+            history_exec(conn, &table_name, id).map_err(context("Failed to read history"))?;
+        }
+        Commands::Undo { op_id } => {
+            match op_id {
+                Some(op_id) => println!("Undoing op {}", op_id),
+                None => println!("Undoing the last operation"),
+            }
+            undo_exec(conn, op_id).map_err(context("Failed to undo operation"))?;
+        }
+        Commands::Sql {
+            query,
+            params,
+            named_params,
+            format,
+            write,
+        } => {
+            //The shell and scripts share a writable connection, so check the statement
+            if !write && !conn.is_readonly(DatabaseName::Main)? && !conn.prepare(&query)?.readonly()
+            {
+                return Err("the statement changes the database; pass --write to run it".into());
+            }
+            let params = QueryParams {
+                positional: params,
+                named: named_params,
+            };
+            let format = format.unwrap_or(config().output_format.value);
+            sql_exec(conn, &query, &params, format).map_err(context("Failed to run query"))?;
+        }
+        Commands::Run {
+            script_path,
+            transaction,
+            on_error,
+        } => {
+            println!("Running script '{}'", script_path);
+            run_script(conn, &script_path, transaction, on_error)
+                .map_err(context("Failed to run script"))?;
+        }
+        Commands::Pipeline {
+            action: PipelineAction::Run { pipeline_path },
+        } => {
+            pipeline_exec(conn, &pipeline_path).map_err(context("Failed to run pipeline"))?;
+        }
+        Commands::Report {
+            workload,
+            iterations,
+            output,
+            baseline,
+            save_profiles,
+        } => {
+            println!("Running benchmark workload for report '{}'", output);
+            run_report(
+                workload.as_deref(),
+                iterations,
+                &output,
+                baseline.as_deref(),
+                save_profiles.as_deref(),
+            )
+            .map_err(context("Failed to write report"))?;
+        }
+        Commands::Compare {
+            csv,
+            python,
+            python_dir,
+            python_db,
+            no_python,
+        } => {
+            println!("Comparing Rust and Python loads of '{}'", csv);
+            let python = PythonSide {
+                interpreter: (!no_python).then_some(python),
+                project_dir: python_dir,
+                database: python_db,
+            };
+            compare_exec(&csv, &python).map_err(context("Failed to compare loaders"))?;
         }
+        Commands::Generate {
+            file_path,
+            rows,
+            seed,
+        } => {
+            generate_exec(&file_path, rows, seed).map_err(context("Failed to generate data"))?;
+        }
+        Commands::Tables => {
+            tables_exec(conn).map_err(context("Failed to list tables"))?;
+        }
+        Commands::Describe { table_name } => {
+            let table_name = table_or_default(table_name)?;
+            describe_exec(conn, &table_name).map_err(context("Failed to describe table"))?;
+        }
+        Commands::Count {
+            table_name,
+            include_deleted,
+        } => {
+            let table_name = table_or_default(table_name)?;
+            count_exec(conn, &table_name, include_deleted)
+                .map_err(context("Failed to count rows"))?;
+        }
+        Commands::Config {
+            action: ConfigAction::Show,
+        } => config_show_exec(&config()),
+        Commands::Repl => return Err("the REPL cannot be started from the REPL or a script".into()),
     }
     Ok(())
 }
 
+//Run a script's commands in order on one connection. With a transaction the
+//commands share a savepoint, so a stop on error leaves the database unchanged.
+fn run_script(
+    conn: &mut Connection,
+    script_path: &str,
+    transaction: bool,
+    on_error: Option<OnError>,
+) -> Result<(), Box<dyn Error>> {
+    let script = read_script(script_path)?;
+    let transaction = transaction || script.transaction;
+    let on_error = on_error.unwrap_or(script.on_error);
+    let total = script.commands.len();
+
+    if transaction {
+        conn.execute_batch("SAVEPOINT sqlite_script")?;
+    }
+    let mut failed = 0;
+    for (i, line) in script.commands.iter().enumerate() {
+        println!("[{}/{}] {}", i + 1, total, line);
+        if let Err(e) = execute_line(conn, line) {
+            report_error(e.as_ref())?;
+            failed += 1;
+            if on_error == OnError::Stop {
+                if transaction {
+                    conn.execute_batch("ROLLBACK TO sqlite_script; RELEASE sqlite_script")?;
+                    println!("Rolled back all commands of the script.");
+                }
+                return Err(format!("command {} of {} failed: {}", i + 1, total, line).into());
+            }
+        }
+    }
+    if transaction {
+        conn.execute_batch("RELEASE sqlite_script")?;
+    }
+    println!(
+        "Script finished: {} commands succeeded, {} failed.",
+        total - failed,
+        failed
+    );
+    Ok(())
+}
+
+//Profile each workload command on one connection and render the results. The
+//workload runs on a scratch database file, so it leaves no tables or audit
+//entries behind in the database in use.
+fn run_report(
+    workload: Option<&str>,
+    iterations: usize,
+    output: &str,
+    baseline: Option<&str>,
+    save_profiles: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let commands = match workload {
+        Some(path) => read_script(path)?.commands,
+        None => DEFAULT_WORKLOAD.map(String::from).to_vec(),
+    };
+    let baseline = baseline
+        .map(|path| read_profiles_csv(path).map(|profiles| summarize(&profiles)))
+        .transpose()?;
+
+    let scratch = std::env::temp_dir().join(format!("sqlite_report_{}.db", std::process::id()));
+    let profiles = (|| -> Result<_, Box<dyn Error>> {
+        let mut conn = open_connection(&scratch)?;
+        let mut profiles = Vec::new();
+        for iteration in 1..=iterations {
+            println!("Iteration {}/{}", iteration, iterations);
+            for line in &commands {
+                let profiler = Profiler::start();
+                execute_line(&mut conn, line).map_err(|e| format!("'{}': {}", line, e))?;
+                profiles.push(profiler.finish(line));
+            }
+        }
+        Ok(profiles)
+    })();
+    for suffix in ["", "-journal", "-wal", "-shm"] {
+        let mut path = scratch.clone().into_os_string();
+        path.push(suffix);
+        //Only the database file is sure to exist
+        let _ = std::fs::remove_file(path);
+    }
+    let profiles = profiles?;
+
+    if let Some(path) = save_profiles {
+        let mut out = BufWriter::new(File::create(path)?);
+        write_profiles(&profiles, ProfileFormat::Csv, true, &mut out)?;
+    }
+    let mut out = BufWriter::new(File::create(output)?);
+    render_report(
+        &Environment::collect(),
+        &summarize(&profiles),
+        baseline.as_deref(),
+        &mut out,
+    )?;
+    println!("Report written to '{}'.", output);
+    Ok(())
+}
+
 fn run_cli_with_profiling(commands: Vec<Commands>) -> Result<()> {
     let start_time = Instant::now();
-    let mut system = System::new_all(); // Initialize sysinfo system
-
     let mut peak_memory: f32 = 0.0;
 
     for command in commands {
         println!("Running command: {:?}", command);
+        let name = format!("{:?}", command)
+            .split([' ', '{'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
 
-        let command_start_time = Instant::now();
-        cli_main(command)?;
-        let command_time = command_start_time.elapsed().as_secs_f32();
-
-        system.refresh_processes();
-        if let Some(process) = system.process((std::process::id() as i32).into()) {
-            let mem_usage = process.memory() as f32 / 1024.0; // Convert KB to MB
-            peak_memory = peak_memory.max(mem_usage);
-
-            println!("Command execution time: {:.2} seconds", command_time);
-            println!("Command memory usage: {:.2} MB", mem_usage);
+        let profiler = Profiler::start();
+        if let Err(e) = cli_main(command) {
+            exit_with_error(e);
         }
+        let profile = profiler.finish(&name);
+
+        let mem_usage = profile.rss_after_kb as f32 / 1024.0; // Convert KB to MB
+        let command_peak = profile.peak_rss_kb as f32 / 1024.0;
+        peak_memory = peak_memory.max(command_peak);
+        println!(
+            "Command execution time: {:.2} seconds",
+            profile.wall_seconds
+        );
+        println!("Command memory usage: {:.2} MB", mem_usage);
+        println!(
+            "Command peak memory usage: {:.2} MB ({} samples)",
+            command_peak,
+            profile.rss_samples.len()
+        );
+        println!("Command rows processed: {}", profile.rows);
     }
 
     let total_time = start_time.elapsed().as_secs_f32();
@@ -205,45 +1103,288 @@ fn run_cli_with_profiling(commands: Vec<Commands>) -> Result<()> {
     Ok(())
 }
 
-// create a list of commands to test
+//Parse command-line words, keeping the subcommand's name for profiles
+fn parse_args<I, T>(words: I) -> Result<(Cli, String), clap::Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString> + Clone,
+{
+    let matches = Cli::command().try_get_matches_from(words)?;
+    let name = matches.subcommand_name().unwrap_or_default().to_string();
+    //Errors raised while building the arguments, such as a bad insert share,
+    //get the same usage text as clap's own
+    let cli = Cli::from_arg_matches(&matches).map_err(|e| {
+        let mut command = Cli::command();
+        command.build();
+        match command.find_subcommand_mut(&name) {
+            Some(subcommand) => e.format(subcommand),
+            None => e.format(&mut command),
+        }
+    })?;
+    Ok((cli, name))
+}
+
+//Run a command, measuring it when --profile was passed
+fn profiled<T>(args: &ProfileArgs, name: &str, f: impl FnOnce() -> T) -> T {
+    let Some(format) = args.profile else {
+        return f();
+    };
+    let profiler = Profiler::with_interval(Duration::from_millis(args.profile_interval));
+    let result = f();
+    let profile = profiler.finish(name);
+    if let Err(e) = write_profile_exec(&profile, format, args.profile_output.as_deref()) {
+        eprintln!("Failed to write profile: {}", e);
+    }
+    result
+}
+
+// run a single command from the command line, or the demo list when no arguments are given
 fn main() -> Result<()> {
+    if std::env::args().len() > 1 {
+        let (args, name) = parse_args(std::env::args_os()).unwrap_or_else(|e| e.exit());
+        if let Some(user) = &args.user {
+            set_audit_user(user);
+        }
+        let mut config = load_config()
+            .map_err(context("Failed to read config"))
+            .unwrap_or_else(|e| exit_with_error(e.into()));
+        config.database.set(args.database.clone(), &Origin::Flag);
+        args.connection
+            .apply(&mut config)
+            .map_err(context("Failed to read connection settings"))
+            .unwrap_or_else(|e| exit_with_error(e.into()));
+        set_connection_settings(config.connection_settings());
+        set_config(config);
+        if let Err(e) = profiled(&args.profile, &name, || cli_main(args.command)) {
+            exit_with_error(e);
+        }
+        return Ok(());
+    }
+
+    let config = load_config()
+        .map_err(context("Failed to read config"))
+        .unwrap_or_else(|e| exit_with_error(e.into()));
+    set_connection_settings(config.connection_settings());
+    set_config(config);
+
+    // create a list of commands to test
     let commands = vec![
-        Commands::Extract {},
+        Commands::Extract {
+            url: None,
+            output: None,
+        },
         Commands::Create {
-            table_name: "test_table2".to_string(),
+            table_name: Some("test_table2".to_string()),
         },
         Commands::Load {
-            table_name: "test_table2".to_string(),
+            table_name: Some("test_table2".to_string()),
             file_path: "data/fifa_countries_audience.csv".to_string(),
+            format: None,
+            csv: CsvArgs::default(),
+            generic: false,
+            sample_rows: DEFAULT_SAMPLE_ROWS,
         },
         Commands::Query {
-            table_name: "test_table2".to_string(),
+            table_name: Some("test_table2".to_string()),
+            include_deleted: false,
+            limit: None,
         },
         Commands::Insert {
-            table_name: "test_table2".to_string(),
-            country: "TestCountry".to_string(),
-            confederation: "TestConfederation".to_string(),
-            population_share: 0.1,
-            tv_audience_share: 0.2,
-            gdp_weighted_share: 0.3,
+            record: InsertArgs {
+                table_name: Some("test_table2".to_string()),
+                country: "TestCountry".to_string(),
+                confederation: "TestConfederation".to_string(),
+                population_share: 0.1,
+                tv_audience_share: 0.2,
+                gdp_weighted_share: 0.3,
+            },
         },
         Commands::Update {
-            table_name: "test_table2".to_string(),
+            table_name: Some("test_table2".to_string()),
             id: 192,
             new_country: Some("TestCountry".to_string()),
             new_confederation: Some("TestConfederation".to_string()),
             new_population_share: Some(1.1),
             new_tv_audience_share: Some(2.2),
             new_gdp_weighted_share: Some(3.3),
+            clear: vec![],
         },
         Commands::Delete {
-            table_name: "test_table2".to_string(),
+            table_name: Some("test_table2".to_string()),
             id: 192,
+            soft: false,
         },
         Commands::Drop {
-            table_name: "test_table2".to_string(),
+            table_name: Some("test_table2".to_string()),
         },
     ];
 
     run_cli_with_profiling(commands)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write_script(name: &str, text: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("sqlite_script_{}_{}.txt", name, std::process::id()));
+        fs::write(&path, text).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    // Puts the process-wide config back when dropped, even if the test fails
+    struct ConfigGuard(Config);
+
+    impl Drop for ConfigGuard {
+        fn drop(&mut self) {
+            set_config(self.0.clone());
+        }
+    }
+
+    fn replace_config(new_config: Config) -> ConfigGuard {
+        let previous = config();
+        set_config(new_config);
+        ConfigGuard(previous)
+    }
+
+    fn countries(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn.prepare("SELECT country FROM t1 ORDER BY id").unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_run_script_mixed() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        create_table(&conn, "t1").unwrap();
+        let path = write_script(
+            "mixed",
+            "insert t1 TestCountry1 TestConfederation 0.1 0.2 0.3\n\
+             -- lowercase SQL runs next to commands\n\
+             insert into t1 (country, tv_audience_share) values ('TestCountry2', 1.5);\n\
+             update t1 set country = 'Renamed' where id = 1;\n\
+             update t1 2 --tv-audience-share 5.5\n\
+             delete from t1 where id = 2;\n\
+             query t1\n",
+        );
+        run_script(&mut conn, &path, false, None).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(countries(&conn), vec!["Renamed"]);
+    }
+
+    #[test]
+    fn test_sql_read_only_on_shared_connection() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        create_table(&conn, "t1").unwrap();
+        execute_line(
+            &mut conn,
+            "insert t1 TestCountry1 TestConfederation 0.1 0.2 0.3",
+        )
+        .unwrap();
+        assert!(execute_line(&mut conn, "sql \"delete from t1\"").is_err());
+        execute_line(&mut conn, "sql \"select * from t1\"").unwrap();
+        assert_eq!(countries(&conn), vec!["TestCountry1"]);
+        execute_line(&mut conn, "sql \"delete from t1\" --write").unwrap();
+        assert!(countries(&conn).is_empty());
+    }
+
+    #[test]
+    fn test_insert_args() {
+        let insert = |words: &[&str]| {
+            let words = ["sqlite", "insert"].iter().chain(words);
+            parse_args(words).map(|(args, _)| match args.command {
+                Commands::Insert { record } => record,
+                other => panic!("parsed {:?}", other),
+            })
+        };
+        let record = InsertArgs {
+            table_name: Some("t1".to_string()),
+            country: "A".to_string(),
+            confederation: "UEFA".to_string(),
+            population_share: 0.1,
+            tv_audience_share: 0.2,
+            gdp_weighted_share: 0.3,
+        };
+        assert_eq!(
+            insert(&["t1", "A", "UEFA", "0.1", "0.2", "0.3"]).unwrap(),
+            record
+        );
+
+        // Five values leave the table to the config file
+        assert_eq!(
+            insert(&["A", "UEFA", "0.1", "0.2", "0.3"]).unwrap(),
+            InsertArgs {
+                table_name: None,
+                ..record
+            }
+        );
+
+        // A bad share names its column
+        let e = insert(&["A", "UEFA", "0.1", "x", "0.3"]).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ValueValidation);
+        assert!(e.to_string().contains("invalid tv_audience_share 'x'"));
+        let e = insert(&["A", "UEFA", "0.1", "0.2"]).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::TooFewValues);
+    }
+
+    #[test]
+    fn test_default_table() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let mut config = Config::default();
+        config
+            .table
+            .set(Some(Some("t1".to_string())), &Origin::Flag);
+        let guard = replace_config(config);
+        for line in [
+            "create",
+            "insert TestCountry1 TestConfederation 0.1 0.2 0.3",
+            "insert t1 TestCountry2 TestConfederation 0.1 0.2 0.3",
+            "update 1 --country Renamed",
+            "delete 2 --soft",
+            "purge 2",
+            "history 1",
+        ] {
+            execute_line(&mut conn, line).unwrap();
+        }
+        drop(guard);
+        assert_eq!(countries(&conn), vec!["Renamed"]);
+
+        assert_eq!(
+            shift_lone_value(Some("t1.csv".to_string()), None, |v| {
+                v.contains('.').then(|| v.to_string())
+            }),
+            (None, Some("t1.csv".to_string()))
+        );
+        assert_eq!(
+            shift_lone_value(Some("t1".to_string()), None, |v| v.parse::<i32>().ok()),
+            (Some("t1".to_string()), None)
+        );
+    }
+
+    #[test]
+    fn test_run_script_transaction_rollback() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        create_table(&conn, "t1").unwrap();
+        let path = write_script(
+            "rollback",
+            "insert t1 TestCountry1 TestConfederation 0.1 0.2 0.3\n\
+             delete from t1 where id = 1;\n\
+             insert t1 TestCountry2 TestConfederation 0.1 0.2 0.3\n\
+             delete from missing_table;\n\
+             insert t1 TestCountry3 TestConfederation 0.1 0.2 0.3\n",
+        );
+        let result = run_script(&mut conn, &path, true, None);
+        assert!(result.is_err());
+        assert!(countries(&conn).is_empty());
+
+        // Without a transaction the statements before the failure stay applied
+        let result = run_script(&mut conn, &path, false, Some(OnError::Continue));
+        fs::remove_file(&path).unwrap();
+        assert!(result.is_ok());
+        assert_eq!(countries(&conn), vec!["TestCountry2", "TestCountry3"]);
+    }
+}
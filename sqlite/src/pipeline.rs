@@ -0,0 +1,373 @@
+// Declarative pipelines: a TOML file naming a source, a target table,
+// load options, validation rules and SQL transforms, run end to end as
+// extract -> create -> load -> validate -> transform with a status per step.
+use crate::generic::{load_generic_csv, DEFAULT_SAMPLE_ROWS};
+use crate::script::reject_yaml;
+use crate::{
+    create_table, drop_table, extract_file_path, extract_from, live_rows_condition, load_data,
+    parse_csv_byte, parse_decimal_separator, CsvOptions, LoadFormat, LoadOptions,
+};
+use rusqlite::Connection;
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Source {
+    // Downloaded by the extract step when given
+    pub url: Option<String>,
+    // File to load; defaults to the url's file name under data/
+    pub path: Option<String>,
+}
+
+impl Source {
+    // The file the load step reads
+    pub fn file_path(&self) -> Option<String> {
+        self.path
+            .clone()
+            .or_else(|| self.url.as_deref().map(extract_file_path))
+    }
+}
+
+// Load options, spelled as on the load subcommand
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoadSettings {
+    pub format: Option<String>,
+    pub delimiter: Option<String>,
+    pub no_header: bool,
+    pub quote: Option<String>,
+    pub comment: Option<String>,
+    pub trim: bool,
+    pub decimal: Option<String>,
+    pub keep_bom: bool,
+    // Infer the table's columns from the CSV header instead of the FIFA schema
+    pub generic: bool,
+    pub sample_rows: Option<usize>,
+    // Drop the table before loading instead of appending to it
+    pub replace: bool,
+}
+
+impl LoadSettings {
+    pub fn csv_options(&self) -> Result<CsvOptions, String> {
+        let defaults = CsvOptions::default();
+        let options = CsvOptions {
+            delimiter: match &self.delimiter {
+                Some(d) => parse_csv_byte(d)?,
+                None => defaults.delimiter,
+            },
+            has_headers: !self.no_header,
+            quote: match &self.quote {
+                Some(q) => parse_csv_byte(q)?,
+                None => defaults.quote,
+            },
+            comment: self.comment.as_deref().map(parse_csv_byte).transpose()?,
+            trim: self.trim,
+            decimal_separator: match &self.decimal {
+                Some(d) => parse_decimal_separator(d)?,
+                None => defaults.decimal_separator,
+            },
+            strip_bom: !self.keep_bom,
+        };
+        options.validate()?;
+        Ok(options)
+    }
+
+    // The configured format, else the one the file extension suggests, else csv
+    pub fn load_format(&self, file_path: &str) -> Result<LoadFormat, String> {
+        match &self.format {
+            Some(format) => format.parse(),
+            None => Ok(LoadFormat::from_path(file_path).unwrap_or(LoadFormat::Csv)),
+        }
+    }
+}
+
+// Rules the loaded table must satisfy; soft-deleted rows are not counted
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Validation {
+    pub min_rows: Option<usize>,
+    // Columns that must have a value in every row
+    pub not_null: Vec<String>,
+    // Columns whose values must not repeat
+    pub unique: Vec<String>,
+    // SQL conditions every row must meet, e.g. "population_share BETWEEN 0 AND 100"
+    pub checks: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pipeline {
+    pub name: Option<String>,
+    pub table: String,
+    pub source: Source,
+    #[serde(default)]
+    pub load: LoadSettings,
+    #[serde(default)]
+    pub validate: Validation,
+    // SQL run in order after validation, e.g. to derive columns or tables
+    #[serde(default)]
+    pub transform: Vec<String>,
+}
+
+// Parse a TOML pipeline definition
+pub fn parse_pipeline(text: &str) -> Result<Pipeline, Box<dyn Error>> {
+    let pipeline: Pipeline = toml::from_str(text)?;
+    if pipeline.source.file_path().is_none() {
+        return Err("pipeline source needs a url or a path".into());
+    }
+    pipeline.load.csv_options()?;
+    Ok(pipeline)
+}
+
+// Read a TOML pipeline file
+pub fn read_pipeline(path: &str) -> Result<Pipeline, Box<dyn Error>> {
+    reject_yaml(path)?;
+    let text = fs::read_to_string(path)?;
+    parse_pipeline(&text)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepStatus {
+    Ok,
+    Skipped(String),
+    Failed(String),
+}
+
+impl fmt::Display for StepStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepStatus::Ok => write!(f, "ok"),
+            StepStatus::Skipped(reason) => write!(f, "skipped ({})", reason),
+            StepStatus::Failed(error) => write!(f, "failed: {}", error),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepReport {
+    pub step: String,
+    pub status: StepStatus,
+    pub elapsed: Duration,
+}
+
+// Runs steps in order, skipping the rest once one has failed
+#[derive(Default)]
+struct StepRunner {
+    reports: Vec<StepReport>,
+}
+
+impl StepRunner {
+    fn failed(&self) -> bool {
+        self.reports
+            .iter()
+            .any(|report| matches!(report.status, StepStatus::Failed(_)))
+    }
+
+    fn skip(&mut self, step: &str, reason: &str) {
+        self.record(
+            step,
+            StepStatus::Skipped(reason.to_string()),
+            Duration::ZERO,
+        );
+    }
+
+    fn run(&mut self, step: &str, f: impl FnOnce() -> Result<(), Box<dyn Error>>) {
+        if self.failed() {
+            return self.skip(step, "an earlier step failed");
+        }
+        println!("Step '{}'", step);
+        let start = Instant::now();
+        let status = match f() {
+            Ok(()) => StepStatus::Ok,
+            Err(e) => StepStatus::Failed(e.to_string()),
+        };
+        self.record(step, status, start.elapsed());
+    }
+
+    fn record(&mut self, step: &str, status: StepStatus, elapsed: Duration) {
+        println!(
+            "Step '{}': {} ({:.2} seconds)",
+            step,
+            status,
+            elapsed.as_secs_f32()
+        );
+        self.reports.push(StepReport {
+            step: step.to_string(),
+            status,
+            elapsed,
+        });
+    }
+}
+
+// Count the live rows of a table that match a condition
+fn count_where(
+    conn: &Connection,
+    table_name: &str,
+    condition: &str,
+) -> Result<i64, Box<dyn Error>> {
+    let live = live_rows_condition(conn, table_name)?;
+    let count = conn.query_row(
+        &format!(
+            "SELECT COUNT(*) FROM {} WHERE {} AND ({})",
+            table_name, live, condition
+        ),
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count)
+}
+
+// Check the validation rules, reporting every rule that fails
+pub fn validate_table(
+    conn: &Connection,
+    table_name: &str,
+    validation: &Validation,
+) -> Result<(), Box<dyn Error>> {
+    let mut failures = Vec::new();
+    if let Some(min_rows) = validation.min_rows {
+        let rows = count_where(conn, table_name, "1 = 1")?;
+        if rows < min_rows as i64 {
+            failures.push(format!(
+                "expected at least {} rows, found {}",
+                min_rows, rows
+            ));
+        }
+    }
+    for column in &validation.not_null {
+        let nulls = count_where(conn, table_name, &format!("{} IS NULL", column))?;
+        if nulls > 0 {
+            failures.push(format!("{} rows have no {}", nulls, column));
+        }
+    }
+    for column in &validation.unique {
+        let live = live_rows_condition(conn, table_name)?;
+        let duplicates: i64 = conn.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM (SELECT {0} FROM {1} WHERE {2} AND {0} IS NOT NULL
+                 GROUP BY {0} HAVING COUNT(*) > 1)",
+                column, table_name, live
+            ),
+            [],
+            |row| row.get(0),
+        )?;
+        if duplicates > 0 {
+            failures.push(format!("{} values of {} are repeated", duplicates, column));
+        }
+    }
+    for check in &validation.checks {
+        let violations = count_where(conn, table_name, &format!("NOT ({})", check))?;
+        if violations > 0 {
+            failures.push(format!("{} rows fail check '{}'", violations, check));
+        }
+    }
+
+    if failures.is_empty() {
+        println!("All validation rules passed for table '{}'.", table_name);
+        Ok(())
+    } else {
+        Err(failures.join("; ").into())
+    }
+}
+
+// Run a pipeline and return the status of each step. The database steps share a
+// savepoint, so when one fails the table is left as it was before the run.
+pub fn run_pipeline(conn: &Connection, pipeline: &Pipeline) -> Vec<StepReport> {
+    let mut runner = StepRunner::default();
+    let table = pipeline.table.as_str();
+    let load = &pipeline.load;
+    // parse_pipeline has checked that a path can be found
+    let file_path = pipeline.source.file_path().unwrap_or_default();
+
+    match &pipeline.source.url {
+        Some(url) => runner.run("extract", || extract_from(url, &file_path)),
+        None => runner.skip("extract", "no source url"),
+    }
+
+    if !runner.failed() {
+        if let Err(e) = conn.execute_batch("SAVEPOINT sqlite_pipeline") {
+            runner.record("begin", StepStatus::Failed(e.to_string()), Duration::ZERO);
+        }
+    }
+    let in_savepoint = !runner.failed();
+
+    runner.run("create", || {
+        if load.replace {
+            drop_table(conn, table)?;
+        }
+        if load.generic {
+            println!("Table '{}' is created from the CSV header on load.", table);
+            Ok(())
+        } else {
+            create_table(conn, table)
+        }
+    });
+    runner.run("load", || {
+        let csv = load.csv_options()?;
+        let format = load.load_format(&file_path)?;
+        if load.generic {
+            if format != LoadFormat::Csv {
+                return Err("generic loads only support csv input".into());
+            }
+            let sample_rows = load.sample_rows.unwrap_or(DEFAULT_SAMPLE_ROWS);
+            load_generic_csv(conn, table, &file_path, &csv, sample_rows)?;
+            Ok(())
+        } else {
+            load_data(conn, table, &file_path, &LoadOptions { format, csv })
+        }
+    });
+    runner.run("validate", || {
+        validate_table(conn, table, &pipeline.validate)
+    });
+    for (i, sql) in pipeline.transform.iter().enumerate() {
+        runner.run(&format!("transform {}", i + 1), || {
+            conn.execute_batch(sql)?;
+            Ok(())
+        });
+    }
+
+    if in_savepoint {
+        let end = if runner.failed() {
+            "ROLLBACK TO sqlite_pipeline; RELEASE sqlite_pipeline"
+        } else {
+            "RELEASE sqlite_pipeline"
+        };
+        if let Err(e) = conn.execute_batch(end) {
+            runner.record("commit", StepStatus::Failed(e.to_string()), Duration::ZERO);
+        } else if runner.failed() {
+            println!("Rolled back the pipeline's changes to the database.");
+        }
+    }
+    runner.reports
+}
+
+// Read and run a pipeline file, printing a summary of its steps
+pub fn pipeline_exec(conn: &Connection, path: &str) -> Result<(), Box<dyn Error>> {
+    let pipeline = read_pipeline(path)?;
+    let name = pipeline.name.clone().unwrap_or_else(|| path.to_string());
+    println!(
+        "Running pipeline '{}' into table '{}'",
+        name, pipeline.table
+    );
+
+    let reports = run_pipeline(conn, &pipeline);
+    println!("\nPipeline '{}' summary:", name);
+    for report in &reports {
+        println!(
+            "  {:<12} {} ({:.2} seconds)",
+            report.step,
+            report.status,
+            report.elapsed.as_secs_f32()
+        );
+    }
+    match reports
+        .iter()
+        .find(|report| matches!(report.status, StepStatus::Failed(_)))
+    {
+        Some(report) => Err(format!("pipeline failed at step '{}'", report.step).into()),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,344 @@
+// Per-command profiling: wall time, CPU time, resident memory and rows processed,
+// written as JSON lines or CSV so results can be compared across runs.
+// Memory is sampled on a background thread while the command runs, so the
+// peak includes allocations that are freed before the command returns.
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use sysinfo::{ProcessExt, System, SystemExt};
+
+// Time between memory samples when no other interval is given
+pub const DEFAULT_SAMPLE_INTERVAL: Duration = Duration::from_millis(10);
+
+// Rows read or written by library functions since the process started
+static ROWS_PROCESSED: AtomicU64 = AtomicU64::new(0);
+
+pub(crate) fn add_rows(rows: usize) {
+    ROWS_PROCESSED.fetch_add(rows as u64, Ordering::Relaxed);
+}
+
+pub fn rows_processed() -> u64 {
+    ROWS_PROCESSED.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProfileFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+impl FromStr for ProfileFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(ProfileFormat::Json),
+            "csv" => Ok(ProfileFormat::Csv),
+            other => Err(format!(
+                "unknown profile format '{}' (expected json or csv)",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandProfile {
+    pub command: String,
+    // Seconds since the Unix epoch when the command started
+    pub started_at: u64,
+    pub wall_seconds: f64,
+    // User plus system CPU time
+    pub cpu_seconds: f64,
+    pub rss_before_kb: u64,
+    pub rss_after_kb: u64,
+    // Highest resident set size seen while the command ran
+    pub peak_rss_kb: u64,
+    pub rows: u64,
+    // Resident set size over the course of the command
+    pub rss_samples: Vec<MemorySample>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemorySample {
+    // Milliseconds since the command started
+    pub elapsed_ms: f64,
+    pub rss_kb: u64,
+}
+
+const CSV_HEADER: [&str; 8] = [
+    "command",
+    "started_at",
+    "wall_seconds",
+    "cpu_seconds",
+    "rss_before_kb",
+    "rss_after_kb",
+    "peak_rss_kb",
+    "rows",
+];
+
+impl CommandProfile {
+    fn csv_record(&self) -> [String; 8] {
+        [
+            self.command.clone(),
+            self.started_at.to_string(),
+            format!("{:.6}", self.wall_seconds),
+            format!("{:.6}", self.cpu_seconds),
+            self.rss_before_kb.to_string(),
+            self.rss_after_kb.to_string(),
+            self.peak_rss_kb.to_string(),
+            self.rows.to_string(),
+        ]
+    }
+
+    // Fields in the same order as the CSV columns, then the memory samples,
+    // which only the JSON output carries
+    fn json(&self) -> String {
+        let values = [
+            serde_json::Value::from(self.command.as_str()),
+            self.started_at.into(),
+            self.wall_seconds.into(),
+            self.cpu_seconds.into(),
+            self.rss_before_kb.into(),
+            self.rss_after_kb.into(),
+            self.peak_rss_kb.into(),
+            self.rows.into(),
+        ];
+        let fields: Vec<String> = CSV_HEADER
+            .iter()
+            .zip(values)
+            .map(|(name, value)| format!("\"{}\":{}", name, value))
+            .collect();
+        let samples: Vec<String> = self
+            .rss_samples
+            .iter()
+            .map(|sample| format!("[{:.3},{}]", sample.elapsed_ms, sample.rss_kb))
+            .collect();
+        format!(
+            "{{{},\"rss_samples\":[{}]}}",
+            fields.join(","),
+            samples.join(",")
+        )
+    }
+}
+
+// CPU time and lifetime peak RSS of this process from getrusage
+#[cfg(unix)]
+fn resource_usage() -> (f64, u64) {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return (0.0, 0);
+    }
+    let seconds = |t: libc::timeval| t.tv_sec as f64 + t.tv_usec as f64 / 1_000_000.0;
+    // ru_maxrss is in kilobytes on Linux but bytes on macOS
+    let peak = if cfg!(target_os = "macos") {
+        usage.ru_maxrss as u64 / 1024
+    } else {
+        usage.ru_maxrss as u64
+    };
+    (seconds(usage.ru_utime) + seconds(usage.ru_stime), peak)
+}
+
+#[cfg(not(unix))]
+fn resource_usage() -> (f64, u64) {
+    (0.0, 0)
+}
+
+// Current resident set size of this process in kilobytes
+pub fn current_rss_kb(system: &mut System) -> u64 {
+    let pid = (std::process::id() as i32).into();
+    system.refresh_process(pid);
+    system.process(pid).map_or(0, |process| process.memory())
+}
+
+// Polls this process's resident set size on a background thread until stopped
+pub struct MemorySampler {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<Vec<MemorySample>>,
+}
+
+impl MemorySampler {
+    pub fn start(interval: Duration) -> MemorySampler {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_flag = Arc::clone(&stop);
+        let handle = thread::spawn(move || {
+            let mut system = System::new();
+            let start = Instant::now();
+            let mut samples = Vec::new();
+            loop {
+                samples.push(MemorySample {
+                    elapsed_ms: start.elapsed().as_secs_f64() * 1000.0,
+                    rss_kb: current_rss_kb(&mut system),
+                });
+                if stop_flag.load(Ordering::Relaxed) {
+                    return samples;
+                }
+                // Woken early by stop()
+                thread::park_timeout(interval);
+            }
+        });
+        MemorySampler { stop, handle }
+    }
+
+    // Stop sampling and return the samples, ending with one taken at the stop
+    pub fn stop(self) -> Vec<MemorySample> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.handle.thread().unpark();
+        self.handle.join().unwrap_or_default()
+    }
+}
+
+// Measures one command: create it before the command runs and finish it after
+pub struct Profiler {
+    system: System,
+    sampler: MemorySampler,
+    started_at: u64,
+    start: Instant,
+    cpu_before: f64,
+    max_rss_before_kb: u64,
+    rss_before_kb: u64,
+    rows_before: u64,
+}
+
+impl Profiler {
+    pub fn start() -> Profiler {
+        Profiler::with_interval(DEFAULT_SAMPLE_INTERVAL)
+    }
+
+    // Sample memory every `interval` while the command runs
+    pub fn with_interval(interval: Duration) -> Profiler {
+        let mut system = System::new();
+        let rss_before_kb = current_rss_kb(&mut system);
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let (cpu_before, max_rss_before_kb) = resource_usage();
+        Profiler {
+            system,
+            sampler: MemorySampler::start(interval),
+            started_at,
+            start: Instant::now(),
+            cpu_before,
+            max_rss_before_kb,
+            rss_before_kb,
+            rows_before: rows_processed(),
+        }
+    }
+
+    pub fn finish(mut self, command: &str) -> CommandProfile {
+        let wall_seconds = self.start.elapsed().as_secs_f64();
+        let rss_samples = self.sampler.stop();
+        let (cpu_after, max_rss_after_kb) = resource_usage();
+        let rss_after_kb = current_rss_kb(&mut self.system);
+
+        let sampled_peak = rss_samples.iter().map(|s| s.rss_kb).max().unwrap_or(0);
+        // A new lifetime high water mark can only have been set during this
+        // command, so it also catches spikes shorter than the sample interval
+        let high_water = if max_rss_after_kb > self.max_rss_before_kb {
+            max_rss_after_kb
+        } else {
+            0
+        };
+        CommandProfile {
+            command: command.to_string(),
+            started_at: self.started_at,
+            wall_seconds,
+            cpu_seconds: cpu_after - self.cpu_before,
+            rss_before_kb: self.rss_before_kb,
+            rss_after_kb,
+            peak_rss_kb: sampled_peak
+                .max(high_water)
+                .max(self.rss_before_kb)
+                .max(rss_after_kb),
+            rows: rows_processed() - self.rows_before,
+            rss_samples,
+        }
+    }
+}
+
+// Write profiles as JSON lines or CSV, with a header when `header` is set
+pub fn write_profiles(
+    profiles: &[CommandProfile],
+    format: ProfileFormat,
+    header: bool,
+    out: &mut dyn Write,
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        ProfileFormat::Json => {
+            for profile in profiles {
+                writeln!(out, "{}", profile.json())?;
+            }
+        }
+        ProfileFormat::Csv => {
+            let mut wtr = csv::Writer::from_writer(&mut *out);
+            if header {
+                wtr.write_record(CSV_HEADER)?;
+            }
+            for profile in profiles {
+                wtr.write_record(profile.csv_record())?;
+            }
+            wtr.flush()?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+// Read profiles written in the CSV format, e.g. to compare against a baseline.
+// Memory samples are not part of the CSV, so they come back empty.
+pub fn read_profiles_csv(path: &str) -> Result<Vec<CommandProfile>, Box<dyn Error>> {
+    let mut rdr = csv::Reader::from_path(path)?;
+    let mut profiles = Vec::new();
+    for (i, result) in rdr.records().enumerate() {
+        let record = result?;
+        let field = |index: usize| -> Result<&str, String> {
+            record
+                .get(index)
+                .ok_or_else(|| format!("record {}: missing {}", i + 1, CSV_HEADER[index]))
+        };
+        let number = |index: usize| -> Result<f64, String> {
+            field(index)?
+                .parse()
+                .map_err(|e| format!("record {}: {}: {}", i + 1, CSV_HEADER[index], e))
+        };
+        profiles.push(CommandProfile {
+            command: field(0)?.to_string(),
+            started_at: number(1)? as u64,
+            wall_seconds: number(2)?,
+            cpu_seconds: number(3)?,
+            rss_before_kb: number(4)? as u64,
+            rss_after_kb: number(5)? as u64,
+            peak_rss_kb: number(6)? as u64,
+            rows: number(7)? as u64,
+            rss_samples: Vec::new(),
+        });
+    }
+    Ok(profiles)
+}
+
+// Append a profile to a file, starting CSV files with a header, or write it to
+// stderr when no path is given so command output on stdout stays clean
+pub fn write_profile_exec(
+    profile: &CommandProfile,
+    format: ProfileFormat,
+    output_path: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    match output_path {
+        Some(path) => {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            let header = file.metadata()?.len() == 0;
+            write_profiles(std::slice::from_ref(profile), format, header, &mut file)
+        }
+        None => {
+            let stderr = io::stderr();
+            let mut out = stderr.lock();
+            write_profiles(std::slice::from_ref(profile), format, true, &mut out)
+        }
+    }
+}
@@ -0,0 +1,379 @@
+//Interactive shell: keeps one connection open and runs CLI commands and SQL
+//statements against it, instead of starting a process per operation
+use crate::{parse_args, profiled, run_command, Cli, Commands};
+use clap::CommandFactory;
+use rusqlite::Connection;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use sqlite::audit::set_audit_user;
+use sqlite::config::{config, set_config};
+use sqlite::connection::{connection_settings, set_connection_settings};
+use sqlite::script::is_comment;
+use sqlite::sql::{run_sql, OutputFormat, QueryParams};
+use std::error::Error;
+use std::io;
+use std::path::PathBuf;
+use std::time::Instant;
+
+const PROMPT: &str = "sqlite> ";
+const CONTINUATION_PROMPT: &str = "   ...> ";
+const HISTORY_FILE: &str = ".sqlite_history";
+
+//Completes command names at the start of a line and table or column names after it
+struct ReplHelper {
+    commands: Vec<String>,
+    names: Vec<String>,
+}
+
+impl ReplHelper {
+    fn new(conn: &Connection) -> ReplHelper {
+        let mut commands = Vec::new();
+        for subcommand in Cli::command().get_subcommands() {
+            commands.push(subcommand.get_name().to_string());
+            commands.extend(subcommand.get_all_aliases().map(String::from));
+        }
+        commands.extend(["exit", "quit"].map(String::from));
+        let mut helper = ReplHelper {
+            commands,
+            names: Vec::new(),
+        };
+        helper.refresh(conn);
+        helper
+    }
+
+    //Re-read table and column names, since commands may have created or dropped tables
+    fn refresh(&mut self, conn: &Connection) {
+        let query = "SELECT m.name FROM sqlite_master m
+            WHERE m.type IN ('table', 'view') AND m.name NOT LIKE 'sqlite_%'
+            UNION
+            SELECT p.name FROM sqlite_master m, pragma_table_info(m.name) p
+            WHERE m.type IN ('table', 'view') AND m.name NOT LIKE 'sqlite_%'";
+        self.names = conn
+            .prepare(query)
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| row.get(0))?
+                    .collect::<rusqlite::Result<Vec<String>>>()
+            })
+            .unwrap_or_default();
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let start = line[..pos]
+            .rfind(|c: char| c.is_whitespace() || "(),;=.".contains(c))
+            .map_or(0, |i| i + 1);
+        let word = line[start..pos].to_lowercase();
+        let candidates = if line[..start].trim().is_empty() {
+            &self.commands
+        } else {
+            &self.names
+        };
+        let matches = candidates
+            .iter()
+            .filter(|candidate| candidate.to_lowercase().starts_with(&word))
+            .map(|candidate| Pair {
+                display: candidate.clone(),
+                replacement: candidate.clone(),
+            })
+            .collect();
+        Ok((start, matches))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+//History is kept in the home directory, or the working directory without one
+fn history_path() -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(HISTORY_FILE)
+}
+
+//Subcommands that share their name with a SQL statement, and the words that
+//make the line SQL when they come second
+const SQL_STATEMENTS: &[(&str, &[&str])] = &[
+    (
+        "create",
+        &[
+            "table",
+            "index",
+            "unique",
+            "view",
+            "trigger",
+            "temp",
+            "temporary",
+            "virtual",
+        ],
+    ),
+    ("drop", &["table", "index", "view", "trigger"]),
+    ("insert", &["into", "or"]),
+    ("delete", &["from"]),
+    ("update", &["or"]),
+];
+
+//Lines ending in ';' and lines that start like a SQL statement are SQL, whatever
+//their case, as are comments
+fn is_sql(line: &str) -> bool {
+    if line.ends_with(';') || is_comment(line) {
+        return true;
+    }
+    let words: Vec<String> = line
+        .split_whitespace()
+        .take(3)
+        .map(str::to_lowercase)
+        .collect();
+    let word = |i: usize| words.get(i).map_or("", String::as_str);
+    //UPDATE's table name comes before SET
+    if word(0) == "update" && word(2) == "set" {
+        return true;
+    }
+    SQL_STATEMENTS
+        .iter()
+        .any(|(keyword, followers)| word(0) == *keyword && followers.contains(&word(1)))
+}
+
+//A line is a command when it starts with a subcommand name, alias or flag and
+//is not SQL; anything else is SQL
+fn is_command(line: &str) -> bool {
+    if is_sql(line) {
+        return false;
+    }
+    let first = line.split_whitespace().next().unwrap_or("");
+    first.starts_with('-') || Cli::command().find_subcommand(first).is_some() || first == "help"
+}
+
+//Parse and run one command line
+fn run_line(conn: &mut Connection, line: &str) -> Result<(), Box<dyn Error>> {
+    let words = shlex::split(line).ok_or("unbalanced quotes")?;
+    let (args, name) = parse_args(std::iter::once("sqlite".to_string()).chain(words))?;
+    if let Some(user) = &args.user {
+        set_audit_user(user);
+    }
+    if args.database.is_some() {
+        return Err("--database cannot change the database of an open shell".into());
+    }
+    //Connection flags retune the shell's connection and later ones
+    let mut config = config();
+    args.connection.apply(&mut config)?;
+    let settings = config.connection_settings();
+    if settings != connection_settings() {
+        settings.apply(conn)?;
+        set_connection_settings(settings);
+        set_config(config);
+    }
+    if let Commands::Repl = args.command {
+        return Err("the REPL cannot be started from the REPL or a script".into());
+    }
+    profiled(&args.profile, &name, || run_command(conn, args.command))
+}
+
+//Run a command line, or a SQL statement when the line is not a command
+pub(crate) fn execute_line(conn: &mut Connection, line: &str) -> Result<(), Box<dyn Error>> {
+    if is_comment(line) {
+        Ok(())
+    } else if is_command(line) {
+        run_line(conn, line)
+    } else {
+        run_statement(conn, line)
+    }
+}
+
+//Print an error without ending the session
+pub(crate) fn report_error(e: &(dyn Error + 'static)) -> io::Result<()> {
+    match e.downcast_ref::<clap::Error>() {
+        //Also covers help and version output
+        Some(e) => e.print(),
+        None => {
+            eprintln!("Error: {}", e);
+            Ok(())
+        }
+    }
+}
+
+//Run a SQL statement on the shell's connection and print its result as a table
+fn run_statement(conn: &Connection, sql: &str) -> Result<(), Box<dyn Error>> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    run_sql(
+        conn,
+        sql,
+        &QueryParams::default(),
+        OutputFormat::Table,
+        &mut out,
+    )?;
+    Ok(())
+}
+
+//Read and run lines until exit, quit or end of input
+pub fn run_repl(conn: &mut Connection) -> Result<(), Box<dyn Error>> {
+    let mut editor: Editor<ReplHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(ReplHelper::new(conn)));
+    let history = history_path();
+    //No history yet on first use
+    let _ = editor.load_history(&history);
+
+    println!("Enter commands such as \"query table1\", or SQL ending in ';'. Type \"help\" for commands, \"exit\" to leave.");
+    let mut sql = String::new();
+    loop {
+        let prompt = if sql.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            //Ctrl-C abandons the statement being typed
+            Err(ReadlineError::Interrupted) => {
+                sql.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(trimmed)?;
+
+        if sql.is_empty() {
+            if matches!(trimmed, "exit" | "quit" | ".exit" | ".quit") {
+                break;
+            }
+            if is_comment(trimmed) {
+                continue;
+            }
+            if is_command(trimmed) {
+                let start = Instant::now();
+                if let Err(e) = run_line(conn, trimmed) {
+                    report_error(e.as_ref())?;
+                }
+                println!(
+                    "Command execution time: {:.3} seconds",
+                    start.elapsed().as_secs_f32()
+                );
+                editor.helper_mut().unwrap().refresh(conn);
+                continue;
+            }
+        }
+
+        //SQL may span lines until a terminating ';'
+        if !sql.is_empty() {
+            sql.push('\n');
+        }
+        sql.push_str(trimmed);
+        if !sql.ends_with(';') {
+            continue;
+        }
+        let start = Instant::now();
+        if let Err(e) = run_statement(conn, &sql) {
+            report_error(e.as_ref())?;
+        }
+        println!(
+            "Command execution time: {:.3} seconds",
+            start.elapsed().as_secs_f32()
+        );
+        sql.clear();
+        editor.helper_mut().unwrap().refresh(conn);
+    }
+
+    editor.save_history(&history)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn completions(helper: &ReplHelper, line: &str) -> (usize, Vec<String>) {
+        let history = DefaultHistory::new();
+        let (start, pairs) = helper
+            .complete(line, line.len(), &Context::new(&history))
+            .unwrap();
+        (
+            start,
+            pairs.into_iter().map(|pair| pair.replacement).collect(),
+        )
+    }
+
+    #[test]
+    fn test_is_command() {
+        for line in [
+            "query t1",
+            "create t1",
+            "drop t1",
+            "delete t1 1",
+            "update t1 1 --country X",
+            "insert t1 TestCountry TestConfederation 0.1 0.2 0.3",
+            "-q t1",
+            "--help",
+            "help",
+        ] {
+            assert!(is_command(line), "{}", line);
+        }
+        for line in [
+            "select * from t1",
+            "delete from t1 where id = 1;",
+            "DELETE FROM t1 WHERE id = 1",
+            "update t1 set country = 'X' where id = 1",
+            "insert into t1 (country) values ('X')",
+            "create table z (a int);",
+            "create unique index z_a on z (a)",
+            "drop table z",
+            "query t1;",
+            "-- comment",
+        ] {
+            assert!(!is_command(line), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_completer() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE countries (id INTEGER, confederation TEXT)")
+            .unwrap();
+        let mut helper = ReplHelper::new(&conn);
+
+        // Command names at the start of a line
+        let (start, words) = completions(&helper, "qu");
+        assert_eq!(start, 0);
+        assert!(words.contains(&"query".to_string()));
+        assert!(words.contains(&"quit".to_string()));
+
+        // Table and column names after it, matched case-insensitively
+        let (start, words) = completions(&helper, "select CONF");
+        assert_eq!(start, 7);
+        assert_eq!(words, vec!["confederation"]);
+        let (_, words) = completions(&helper, "query cou");
+        assert_eq!(words, vec!["countries"]);
+
+        // Tables created later appear after a refresh
+        conn.execute_batch("CREATE TABLE cities (id INTEGER)")
+            .unwrap();
+        assert!(completions(&helper, "query ci").1.is_empty());
+        helper.refresh(&conn);
+        assert_eq!(completions(&helper, "query ci").1, vec!["cities"]);
+    }
+}
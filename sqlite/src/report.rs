@@ -0,0 +1,207 @@
+// Markdown performance reports built from command profiles: environment details,
+// a timing and memory table per command, and the change against a baseline run.
+use crate::profile::CommandProfile;
+use std::error::Error;
+use std::io::Write;
+use sysinfo::{CpuExt, System, SystemExt};
+
+// Where the report was produced
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Environment {
+    pub os: String,
+    pub kernel: String,
+    pub cpu: String,
+    pub cpu_cores: usize,
+    pub total_memory_kb: u64,
+    pub build_profile: String,
+    pub sqlite_version: String,
+    pub crate_version: String,
+}
+
+impl Environment {
+    pub fn collect() -> Environment {
+        let mut system = System::new();
+        system.refresh_cpu();
+        system.refresh_memory();
+        let unknown = || "unknown".to_string();
+        Environment {
+            os: system.long_os_version().unwrap_or_else(unknown),
+            kernel: system.kernel_version().unwrap_or_else(unknown),
+            cpu: system
+                .cpus()
+                .first()
+                .map(|cpu| cpu.brand().trim().to_string())
+                .filter(|brand| !brand.is_empty())
+                .unwrap_or_else(unknown),
+            cpu_cores: system.physical_core_count().unwrap_or(system.cpus().len()),
+            total_memory_kb: system.total_memory(),
+            build_profile: if cfg!(debug_assertions) {
+                "debug".to_string()
+            } else {
+                "release".to_string()
+            },
+            sqlite_version: rusqlite::version().to_string(),
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+// Profiles of one command aggregated over every run
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandSummary {
+    pub command: String,
+    pub runs: usize,
+    pub mean_wall_seconds: f64,
+    pub min_wall_seconds: f64,
+    pub max_wall_seconds: f64,
+    pub mean_cpu_seconds: f64,
+    pub peak_rss_kb: u64,
+    pub rows: u64,
+}
+
+// Group profiles by command, in the order commands first appear
+pub fn summarize(profiles: &[CommandProfile]) -> Vec<CommandSummary> {
+    let mut summaries: Vec<CommandSummary> = Vec::new();
+    for profile in profiles {
+        match summaries.iter_mut().find(|s| s.command == profile.command) {
+            Some(summary) => {
+                let runs = summary.runs as f64;
+                summary.mean_wall_seconds =
+                    (summary.mean_wall_seconds * runs + profile.wall_seconds) / (runs + 1.0);
+                summary.mean_cpu_seconds =
+                    (summary.mean_cpu_seconds * runs + profile.cpu_seconds) / (runs + 1.0);
+                summary.min_wall_seconds = summary.min_wall_seconds.min(profile.wall_seconds);
+                summary.max_wall_seconds = summary.max_wall_seconds.max(profile.wall_seconds);
+                summary.peak_rss_kb = summary.peak_rss_kb.max(profile.peak_rss_kb);
+                summary.rows = summary.rows.max(profile.rows);
+                summary.runs += 1;
+            }
+            None => summaries.push(CommandSummary {
+                command: profile.command.clone(),
+                runs: 1,
+                mean_wall_seconds: profile.wall_seconds,
+                min_wall_seconds: profile.wall_seconds,
+                max_wall_seconds: profile.wall_seconds,
+                mean_cpu_seconds: profile.cpu_seconds,
+                peak_rss_kb: profile.peak_rss_kb,
+                rows: profile.rows,
+            }),
+        }
+    }
+    summaries
+}
+
+fn milliseconds(seconds: f64) -> String {
+    format!("{:.2}", seconds * 1000.0)
+}
+
+fn megabytes(kb: u64) -> String {
+    format!("{:.2}", kb as f64 / 1024.0)
+}
+
+// Keep table cells on one line and free of column separators
+fn cell(text: &str) -> String {
+    format!("`{}`", text.replace('|', "\\|").replace('`', "'"))
+}
+
+// Render the report as Markdown. With a baseline, a comparison table shows how
+// each command's mean wall time and peak memory changed.
+pub fn render_report(
+    environment: &Environment,
+    summaries: &[CommandSummary],
+    baseline: Option<&[CommandSummary]>,
+    out: &mut dyn Write,
+) -> Result<(), Box<dyn Error>> {
+    writeln!(out, "# Performance Report")?;
+    writeln!(out)?;
+    writeln!(out, "## Environment")?;
+    writeln!(out)?;
+    writeln!(out, "| Property | Value |")?;
+    writeln!(out, "| --- | --- |")?;
+    writeln!(out, "| OS | {} |", environment.os)?;
+    writeln!(out, "| Kernel | {} |", environment.kernel)?;
+    writeln!(out, "| CPU | {} |", environment.cpu)?;
+    writeln!(out, "| CPU cores | {} |", environment.cpu_cores)?;
+    writeln!(
+        out,
+        "| Memory | {} MB |",
+        megabytes(environment.total_memory_kb)
+    )?;
+    writeln!(out, "| Build profile | {} |", environment.build_profile)?;
+    writeln!(out, "| SQLite | {} |", environment.sqlite_version)?;
+    writeln!(out, "| sqlite crate | {} |", environment.crate_version)?;
+    writeln!(out)?;
+
+    writeln!(out, "## Commands")?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "| Command | Runs | Mean wall (ms) | Min wall (ms) | Max wall (ms) | Mean CPU (ms) | Peak RSS (MB) | Rows |"
+    )?;
+    writeln!(
+        out,
+        "| --- | ---: | ---: | ---: | ---: | ---: | ---: | ---: |"
+    )?;
+    for summary in summaries {
+        writeln!(
+            out,
+            "| {} | {} | {} | {} | {} | {} | {} | {} |",
+            cell(&summary.command),
+            summary.runs,
+            milliseconds(summary.mean_wall_seconds),
+            milliseconds(summary.min_wall_seconds),
+            milliseconds(summary.max_wall_seconds),
+            milliseconds(summary.mean_cpu_seconds),
+            megabytes(summary.peak_rss_kb),
+            summary.rows
+        )?;
+    }
+    let total: f64 = summaries
+        .iter()
+        .map(|s| s.mean_wall_seconds * s.runs as f64)
+        .sum();
+    let peak = summaries.iter().map(|s| s.peak_rss_kb).max().unwrap_or(0);
+    writeln!(out)?;
+    writeln!(
+        out,
+        "Total wall time: {} ms. Peak RSS: {} MB.",
+        milliseconds(total),
+        megabytes(peak)
+    )?;
+
+    if let Some(baseline) = baseline {
+        writeln!(out)?;
+        writeln!(out, "## Comparison with baseline")?;
+        writeln!(out)?;
+        writeln!(
+            out,
+            "| Command | Baseline wall (ms) | Wall (ms) | Change | Baseline peak RSS (MB) | Peak RSS (MB) |"
+        )?;
+        writeln!(out, "| --- | ---: | ---: | ---: | ---: | ---: |")?;
+        for summary in summaries {
+            let Some(before) = baseline.iter().find(|b| b.command == summary.command) else {
+                continue;
+            };
+            let change = if before.mean_wall_seconds > 0.0 {
+                format!(
+                    "{:+.1}%",
+                    (summary.mean_wall_seconds / before.mean_wall_seconds - 1.0) * 100.0
+                )
+            } else {
+                "n/a".to_string()
+            };
+            writeln!(
+                out,
+                "| {} | {} | {} | {} | {} | {} |",
+                cell(&summary.command),
+                milliseconds(before.mean_wall_seconds),
+                milliseconds(summary.mean_wall_seconds),
+                change,
+                megabytes(before.peak_rss_kb),
+                megabytes(summary.peak_rss_kb)
+            )?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
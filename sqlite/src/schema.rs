@@ -0,0 +1,225 @@
+// Table introspection: which tables exist, their columns, constraints and
+// indexes, and how many rows they hold. Functions return structured data; the
+// *_exec wrappers print it for the CLI.
+use crate::{count_rows, live_rows_condition};
+use rusqlite::Connection;
+use std::error::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableSummary {
+    pub name: String,
+    // "table" or "view"
+    pub kind: String,
+    pub columns: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnInfo {
+    pub name: String,
+    // Declared type, empty when none was declared
+    pub data_type: String,
+    pub not_null: bool,
+    // Default value as SQL text
+    pub default: Option<String>,
+    // Position in the primary key, starting at 1; 0 when not part of it
+    pub primary_key: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexInfo {
+    pub name: String,
+    pub unique: bool,
+    // "c" for CREATE INDEX, "u" for a UNIQUE constraint, "pk" for the primary key
+    pub origin: String,
+    pub columns: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableDescription {
+    pub name: String,
+    pub columns: Vec<ColumnInfo>,
+    pub indexes: Vec<IndexInfo>,
+    // Rows that are not soft-deleted
+    pub rows: usize,
+    pub deleted_rows: usize,
+}
+
+// User tables and views by name, leaving out SQLite's internal tables
+pub fn list_tables(conn: &Connection) -> Result<Vec<TableSummary>, Box<dyn Error>> {
+    let mut stmt = conn.prepare(
+        "SELECT m.name, m.type, (SELECT COUNT(*) FROM pragma_table_info(m.name))
+         FROM sqlite_master m
+         WHERE m.type IN ('table', 'view') AND m.name NOT LIKE 'sqlite_%'
+         ORDER BY m.name",
+    )?;
+    let tables = stmt
+        .query_map([], |row| {
+            Ok(TableSummary {
+                name: row.get(0)?,
+                kind: row.get(1)?,
+                columns: row.get::<_, i64>(2)? as usize,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(tables)
+}
+
+fn ensure_table_exists(conn: &Connection, table_name: &str) -> Result<(), Box<dyn Error>> {
+    let exists: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type IN ('table', 'view') AND name = ?",
+        [table_name],
+        |row| row.get(0),
+    )?;
+    if exists {
+        Ok(())
+    } else {
+        Err(format!("no such table '{}'", table_name).into())
+    }
+}
+
+// Rows in a table, leaving out soft-deleted records unless `include_deleted`
+pub fn count_table_rows(
+    conn: &Connection,
+    table_name: &str,
+    include_deleted: bool,
+) -> Result<usize, Box<dyn Error>> {
+    ensure_table_exists(conn, table_name)?;
+    let condition = if include_deleted {
+        "1 = 1".to_string()
+    } else {
+        live_rows_condition(conn, table_name)?
+    };
+    count_rows(conn, table_name, &condition)
+}
+
+fn table_indexes(conn: &Connection, table_name: &str) -> Result<Vec<IndexInfo>, Box<dyn Error>> {
+    let mut stmt =
+        conn.prepare("SELECT name, \"unique\", origin FROM pragma_index_list(?) ORDER BY name")?;
+    let mut indexes = stmt
+        .query_map([table_name], |row| {
+            Ok(IndexInfo {
+                name: row.get(0)?,
+                unique: row.get(1)?,
+                origin: row.get(2)?,
+                columns: Vec::new(),
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut stmt = conn.prepare("SELECT name FROM pragma_index_info(?) ORDER BY seqno")?;
+    for index in &mut indexes {
+        index.columns = stmt
+            .query_map([&index.name], |row| {
+                // NULL for expression columns
+                Ok(row
+                    .get::<_, Option<String>>(0)?
+                    .unwrap_or_else(|| "<expression>".to_string()))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+    }
+    Ok(indexes)
+}
+
+// Columns, indexes and row counts of a table
+pub fn describe_table(
+    conn: &Connection,
+    table_name: &str,
+) -> Result<TableDescription, Box<dyn Error>> {
+    ensure_table_exists(conn, table_name)?;
+    let mut stmt = conn.prepare(
+        "SELECT name, type, \"notnull\", dflt_value, pk FROM pragma_table_info(?) ORDER BY cid",
+    )?;
+    let columns = stmt
+        .query_map([table_name], |row| {
+            Ok(ColumnInfo {
+                name: row.get(0)?,
+                data_type: row.get(1)?,
+                not_null: row.get(2)?,
+                default: row.get(3)?,
+                primary_key: row.get::<_, i64>(4)? as usize,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let rows = count_table_rows(conn, table_name, false)?;
+    let all_rows = count_table_rows(conn, table_name, true)?;
+    Ok(TableDescription {
+        name: table_name.to_string(),
+        columns,
+        indexes: table_indexes(conn, table_name)?,
+        rows,
+        deleted_rows: all_rows - rows,
+    })
+}
+
+// Print the tables and views in the database
+pub fn tables_exec(conn: &Connection) -> Result<(), Box<dyn Error>> {
+    let tables = list_tables(conn)?;
+    if tables.is_empty() {
+        println!("No tables.");
+    }
+    for table in &tables {
+        let kind = if table.kind == "view" { " (view)" } else { "" };
+        println!("{}{}: {} columns", table.name, kind, table.columns);
+    }
+    Ok(())
+}
+
+// Print a table's columns with their constraints, its indexes and row count
+pub fn describe_exec(conn: &Connection, table_name: &str) -> Result<(), Box<dyn Error>> {
+    let description = describe_table(conn, table_name)?;
+    println!("Table: {}", description.name);
+    println!("Columns:");
+    for column in &description.columns {
+        let mut constraints = Vec::new();
+        if column.primary_key > 0 {
+            constraints.push("PRIMARY KEY".to_string());
+        }
+        if column.not_null {
+            constraints.push("NOT NULL".to_string());
+        }
+        if let Some(default) = &column.default {
+            constraints.push(format!("DEFAULT {}", default));
+        }
+        let data_type = if column.data_type.is_empty() {
+            "(no type)"
+        } else {
+            &column.data_type
+        };
+        println!(
+            "  {} {}{}{}",
+            column.name,
+            data_type,
+            if constraints.is_empty() { "" } else { " " },
+            constraints.join(" ")
+        );
+    }
+    if description.indexes.is_empty() {
+        println!("Indexes: none");
+    } else {
+        println!("Indexes:");
+        for index in &description.indexes {
+            println!(
+                "  {}{} ({})",
+                index.name,
+                if index.unique { " UNIQUE" } else { "" },
+                index.columns.join(", ")
+            );
+        }
+    }
+    println!(
+        "Rows: {} ({} soft-deleted)",
+        description.rows, description.deleted_rows
+    );
+    Ok(())
+}
+
+// Print the number of rows in a table
+pub fn count_exec(
+    conn: &Connection,
+    table_name: &str,
+    include_deleted: bool,
+) -> Result<(), Box<dyn Error>> {
+    let rows = count_table_rows(conn, table_name, include_deleted)?;
+    println!("Table '{}' has {} rows.", table_name, rows);
+    Ok(())
+}
@@ -0,0 +1,107 @@
+// Batch scripts: a list of CLI-style commands to run in order on one connection.
+// A script is a plain text file with one command per line, or a TOML file that
+// can also set whether to use a transaction and what to do on errors.
+use serde::Deserialize;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptFormat {
+    Lines,
+    Toml,
+}
+
+impl ScriptFormat {
+    // Choose the format from a file extension; anything else is one command per line
+    pub fn from_path(path: &str) -> ScriptFormat {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+        match extension.as_deref() {
+            Some("toml") => ScriptFormat::Toml,
+            _ => ScriptFormat::Lines,
+        }
+    }
+}
+
+// YAML files are refused rather than misread as another format
+pub(crate) fn reject_yaml(path: &str) -> Result<(), Box<dyn Error>> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    match extension.as_deref() {
+        Some("yaml") | Some("yml") => {
+            Err(format!("{}: YAML is not supported; write the file as TOML", path).into())
+        }
+        _ => Ok(()),
+    }
+}
+
+// What to do when a command fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnError {
+    #[default]
+    Stop,
+    Continue,
+}
+
+impl FromStr for OnError {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "stop" => Ok(OnError::Stop),
+            "continue" => Ok(OnError::Continue),
+            other => Err(format!(
+                "unknown error mode '{}' (expected stop or continue)",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct Script {
+    // Run every command inside one transaction, committed at the end
+    #[serde(default)]
+    pub transaction: bool,
+    #[serde(default)]
+    pub on_error: OnError,
+    pub commands: Vec<String>,
+}
+
+// A SQL comment, skipped rather than run by scripts and the shell. The space
+// tells it apart from a flag such as --help.
+pub fn is_comment(line: &str) -> bool {
+    line == "--" || line.starts_with("-- ")
+}
+
+// Parse a script; blank lines, lines starting with # and SQL comments are skipped in
+// the line format
+pub fn parse_script(text: &str, format: ScriptFormat) -> Result<Script, Box<dyn Error>> {
+    let script = match format {
+        ScriptFormat::Lines => Script {
+            commands: text
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#') && !is_comment(line))
+                .map(String::from)
+                .collect(),
+            ..Script::default()
+        },
+        ScriptFormat::Toml => toml::from_str(text)?,
+    };
+    Ok(script)
+}
+
+// Read a script file, choosing its format from the extension
+pub fn read_script(path: &str) -> Result<Script, Box<dyn Error>> {
+    reject_yaml(path)?;
+    let text = fs::read_to_string(path)?;
+    parse_script(&text, ScriptFormat::from_path(path))
+}
@@ -0,0 +1,294 @@
+// Ad-hoc SQL: run a user query with bound parameters and render the result set.
+// The CLI opens a read-only connection for this unless writes are asked for.
+use crate::profile;
+use rusqlite::types::Value;
+use rusqlite::{Connection, Statement};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufWriter, Write};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Csv,
+    Json,
+    Ndjson,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(OutputFormat::Table),
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" | "jsonl" => Ok(OutputFormat::Ndjson),
+            other => Err(format!(
+                "unknown output format '{}' (expected table, csv, json or ndjson)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OutputFormat::Table => "table",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::Ndjson => "ndjson",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Values bound to a query's parameters
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryParams {
+    // Bound in order to ?1, ?2, ... (or plain ?)
+    pub positional: Vec<Value>,
+    // Bound by name; the name may leave out its :, @ or $ prefix
+    pub named: Vec<(String, Value)>,
+}
+
+// Parse a parameter from the command line: integers and reals become numbers,
+// NULL (any case) becomes NULL and everything else is text
+pub fn parse_param_value(s: &str) -> Value {
+    if s.eq_ignore_ascii_case("null") {
+        Value::Null
+    } else if let Ok(i) = s.parse::<i64>() {
+        Value::Integer(i)
+    } else if let Ok(f) = s.parse::<f64>() {
+        Value::Real(f)
+    } else {
+        Value::Text(s.to_string())
+    }
+}
+
+// Parse a "name=value" named parameter
+pub fn parse_named_param(s: &str) -> Result<(String, Value), String> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), parse_param_value(value))),
+        _ => Err(format!("expected name=value, found '{}'", s)),
+    }
+}
+
+// Bind positional values to the ? and ?NNN parameters in order, and named values
+// to :name, @name or $name, failing if any parameter is left unbound
+fn bind_params(stmt: &mut Statement, params: &QueryParams) -> Result<(), Box<dyn Error>> {
+    let mut bound = vec![false; stmt.parameter_count()];
+
+    let mut positional = params.positional.iter();
+    for index in 1..=stmt.parameter_count() {
+        let is_positional = stmt
+            .parameter_name(index)
+            .is_none_or(|name| name.starts_with('?'));
+        if is_positional {
+            if let Some(value) = positional.next() {
+                stmt.raw_bind_parameter(index, value)?;
+                bound[index - 1] = true;
+            }
+        }
+    }
+    if positional.next().is_some() {
+        return Err(format!(
+            "query takes {} positional parameters, got {}",
+            bound.iter().filter(|b| **b).count(),
+            params.positional.len()
+        )
+        .into());
+    }
+
+    for (name, value) in &params.named {
+        let index = if name.starts_with([':', '@', '$']) {
+            stmt.parameter_index(name)?
+        } else {
+            [':', '@', '$']
+                .iter()
+                .map(|prefix| stmt.parameter_index(&format!("{}{}", prefix, name)))
+                .find_map(|index| index.transpose())
+                .transpose()?
+        };
+        let index = index.ok_or_else(|| format!("query has no parameter named '{}'", name))?;
+        stmt.raw_bind_parameter(index, value)?;
+        bound[index - 1] = true;
+    }
+
+    if let Some(index) = bound.iter().position(|b| !b) {
+        let name = stmt.parameter_name(index + 1).unwrap_or("?");
+        return Err(format!("no value given for parameter {} ({})", index + 1, name).into());
+    }
+    Ok(())
+}
+
+// Render a value as plain text for table and CSV output
+fn text_value(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Integer(i) => i.to_string(),
+        Value::Real(f) => f.to_string(),
+        Value::Text(s) => s.clone(),
+        Value::Blob(b) => format!("<{} bytes>", b.len()),
+    }
+}
+
+// Render a value as JSON
+fn json_value(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Null => serde_json::Value::Null,
+        Value::Integer(i) => (*i).into(),
+        Value::Real(f) => serde_json::Number::from_f64(*f)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        Value::Text(s) => s.clone().into(),
+        Value::Blob(b) => b
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>()
+            .into(),
+    }
+}
+
+// A row as a JSON object, keeping the query's column order
+fn json_object(names: &[String], values: &[Value]) -> String {
+    let fields: Vec<String> = names
+        .iter()
+        .zip(values)
+        .map(|(name, value)| {
+            format!(
+                "{}:{}",
+                serde_json::Value::from(name.as_str()),
+                json_value(value)
+            )
+        })
+        .collect();
+    format!("{{{}}}", fields.join(","))
+}
+
+// Write rows as an aligned text table
+fn write_table(
+    out: &mut dyn Write,
+    names: &[String],
+    rows: &[Vec<String>],
+) -> Result<(), Box<dyn Error>> {
+    let mut widths: Vec<usize> = names.iter().map(|name| name.chars().count()).collect();
+    for row in rows {
+        for (width, field) in widths.iter_mut().zip(row) {
+            *width = (*width).max(field.chars().count());
+        }
+    }
+    let line = |fields: &[String]| -> String {
+        fields
+            .iter()
+            .zip(&widths)
+            .map(|(field, width)| format!("{:<width$}", field, width = width))
+            .collect::<Vec<_>>()
+            .join(" | ")
+    };
+    writeln!(out, "{}", line(names))?;
+    let separator: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    writeln!(out, "{}", separator.join("-+-"))?;
+    for row in rows {
+        writeln!(out, "{}", line(row))?;
+    }
+    Ok(())
+}
+
+// Run a query and write its result set to `out`, returning the number of rows.
+// Statements that return no columns report the number of rows they changed instead.
+pub fn run_sql(
+    conn: &Connection,
+    query: &str,
+    params: &QueryParams,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<usize, Box<dyn Error>> {
+    let mut stmt = conn.prepare(query)?;
+    bind_params(&mut stmt, params)?;
+
+    if stmt.column_count() == 0 {
+        let changed = stmt.raw_execute()?;
+        writeln!(out, "{} rows changed.", changed)?;
+        profile::add_rows(changed);
+        return Ok(changed);
+    }
+
+    let names: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let mut rows = stmt.raw_query();
+    let mut count = 0;
+    let mut next_values = || -> Result<Option<Vec<Value>>, Box<dyn Error>> {
+        match rows.next()? {
+            Some(row) => Ok(Some(
+                (0..names.len())
+                    .map(|i| row.get::<_, Value>(i))
+                    .collect::<Result<Vec<_>, _>>()?,
+            )),
+            None => Ok(None),
+        }
+    };
+
+    match format {
+        OutputFormat::Table => {
+            // Column widths depend on every row, so the table is rendered at the end
+            let mut table_rows = Vec::new();
+            while let Some(values) = next_values()? {
+                table_rows.push(values.iter().map(text_value).collect::<Vec<_>>());
+                count += 1;
+            }
+            write_table(out, &names, &table_rows)?;
+            writeln!(out, "({} rows)", count)?;
+        }
+        OutputFormat::Csv => {
+            let mut wtr = csv::Writer::from_writer(&mut *out);
+            wtr.write_record(&names)?;
+            while let Some(values) = next_values()? {
+                wtr.write_record(values.iter().map(|value| match value {
+                    Value::Null => String::new(),
+                    other => text_value(other),
+                }))?;
+                count += 1;
+            }
+            wtr.flush()?;
+        }
+        OutputFormat::Json => {
+            writeln!(out, "[")?;
+            while let Some(values) = next_values()? {
+                if count > 0 {
+                    writeln!(out, ",")?;
+                }
+                write!(out, "  {}", json_object(&names, &values))?;
+                count += 1;
+            }
+            if count > 0 {
+                writeln!(out)?;
+            }
+            writeln!(out, "]")?;
+        }
+        OutputFormat::Ndjson => {
+            while let Some(values) = next_values()? {
+                writeln!(out, "{}", json_object(&names, &values))?;
+                count += 1;
+            }
+        }
+    }
+    out.flush()?;
+    profile::add_rows(count);
+    Ok(count)
+}
+
+// Run a query and print its result set to stdout
+pub fn sql_exec(
+    conn: &Connection,
+    query: &str,
+    params: &QueryParams,
+    format: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    run_sql(conn, query, params, format, &mut out)?;
+    Ok(())
+}
@@ -2,14 +2,33 @@
 mod tests {
     use once_cell::sync::Lazy;
     use rusqlite::Connection;
-    use sqlite::UpdateFields;
+    use sqlite::audit::{record_history, undo_exec, AuditOperation};
+    use sqlite::backup::{backup_exec, restore_backup_exec};
+    use sqlite::compare::{compare_tables, time_rust_load, PYTHON_TABLE};
+    use sqlite::config::{Config, Origin};
+    use sqlite::connection::{
+        connection_settings, set_connection_settings, ConnectionSettings, JournalMode, Synchronous,
+    };
+    use sqlite::export::{export_table, ExportFormat};
+    use sqlite::generate::generate_rows;
+    use sqlite::generic::{load_generic_csv, ColumnType};
+    use sqlite::pipeline::{parse_pipeline, run_pipeline, StepStatus};
+    use sqlite::profile::{write_profiles, CommandProfile, MemorySampler, ProfileFormat, Profiler};
+    use sqlite::report::{render_report, summarize, Environment};
+    use sqlite::schema::{count_table_rows, describe_table, list_tables};
+    use sqlite::script::{parse_script, read_script, OnError, ScriptFormat};
+    use sqlite::sql::{parse_named_param, parse_param_value, run_sql, OutputFormat, QueryParams};
     use sqlite::{
-        create_exec, create_table, delete_exec, drop_table, extract, load_data_from_csv, read_exec,
-        update_exec,
+        create_exec, create_table, delete_exec, drop_table, extract, extract_file_path,
+        for_each_country, load_data_from_csv, load_data_from_json, load_data_from_ndjson,
+        load_data_from_reader, purge_exec, read_exec, restore_exec, soft_delete_exec, update_exec,
     };
+    use sqlite::{CsvOptions, FifaCountry, LoadFormat, LoadOptions, UpdateFields};
     use std::error::Error;
+    use std::ops::ControlFlow;
     use std::path::Path;
     use std::sync::Mutex;
+    use std::time::Duration;
 
     static DB_MUTEX: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
 
@@ -102,9 +121,9 @@ mod tests {
         .unwrap();
 
         let fields = UpdateFields {
-            new_country: Some("UpdatedCountry"),
+            new_country: Some(Some("UpdatedCountry")),
             new_confederation: None,
-            new_population_share: Some(4.4),
+            new_population_share: Some(Some(4.4)),
             new_tv_audience_share: None,
             new_gdp_weighted_share: None,
         };
@@ -115,6 +134,42 @@ mod tests {
         teardown_db(&conn);
     }
 
+    #[test]
+    fn test_update_exec_partial_and_clear() {
+        let _lock = DB_MUTEX.lock().unwrap();
+        let conn = setup_db();
+        create_exec(
+            &conn,
+            "test_table",
+            "TestCountry",
+            "TestConfederation",
+            1.1,
+            2.2,
+            3.3,
+        )
+        .unwrap();
+
+        let fields = UpdateFields {
+            new_confederation: Some(None),
+            new_tv_audience_share: Some(Some(5.5)),
+            ..Default::default()
+        };
+        update_exec(&conn, "test_table", 1, fields).expect("Failed to update record");
+
+        let (country, confederation, tv_audience_share): (String, Option<String>, f64) = conn
+            .query_row(
+                "SELECT country, confederation, tv_audience_share FROM test_table WHERE id = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(country, "TestCountry");
+        assert_eq!(confederation, None);
+        assert_eq!(tv_audience_share, 5.5);
+        assert!(read_exec(&conn, "test_table").is_ok());
+        teardown_db(&conn);
+    }
+
     #[test]
     fn test_delete_exec() {
         let _lock = DB_MUTEX.lock().unwrap();
@@ -170,4 +225,1080 @@ mod tests {
 
         teardown_db(&conn);
     }
+
+    #[test]
+    fn test_record_history() {
+        let conn = Connection::open_in_memory().expect("Failed to open database");
+        create_table(&conn, "test_table").expect("Failed to create table");
+        create_exec(
+            &conn,
+            "test_table",
+            "TestCountry",
+            "TestConfederation",
+            1.1,
+            2.2,
+            3.3,
+        )
+        .unwrap();
+        let fields = UpdateFields {
+            new_population_share: Some(Some(4.4)),
+            ..Default::default()
+        };
+        update_exec(&conn, "test_table", 1, fields).unwrap();
+        delete_exec(&conn, "test_table", 1).unwrap();
+
+        let history = record_history(&conn, "test_table", 1).unwrap();
+        let operations: Vec<AuditOperation> = history.iter().map(|e| e.operation).collect();
+        assert_eq!(
+            operations,
+            vec![
+                AuditOperation::Insert,
+                AuditOperation::Update,
+                AuditOperation::Delete
+            ]
+        );
+        assert!(history[0].before_values.is_none());
+        assert!(history[1]
+            .before_values
+            .as_deref()
+            .unwrap()
+            .contains("\"population_share\":1.1"));
+        assert!(history[1]
+            .after_values
+            .as_deref()
+            .unwrap()
+            .contains("\"population_share\":4.4"));
+        assert!(history[2].after_values.is_none());
+    }
+
+    #[test]
+    fn test_undo_exec() {
+        let conn = Connection::open_in_memory().expect("Failed to open database");
+        create_table(&conn, "test_table").expect("Failed to create table");
+        load_data_from_csv(&conn, "test_table", "data/fifa_countries_audience.csv").unwrap();
+        let fields = UpdateFields {
+            new_country: Some(Some("UpdatedCountry")),
+            ..Default::default()
+        };
+        update_exec(&conn, "test_table", 1, fields).unwrap();
+        delete_exec(&conn, "test_table", 2).unwrap();
+
+        let count = |conn: &Connection| -> i64 {
+            conn.query_row("SELECT COUNT(*) FROM test_table", [], |row| row.get(0))
+                .unwrap()
+        };
+        let country = |conn: &Connection| -> String {
+            conn.query_row("SELECT country FROM test_table WHERE id = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+        };
+
+        // Undo the delete, then the update, then the load
+        undo_exec(&conn, None).unwrap();
+        assert_eq!(count(&conn), 191);
+        undo_exec(&conn, None).unwrap();
+        assert_eq!(country(&conn), "United States");
+        undo_exec(&conn, None).unwrap();
+        assert_eq!(count(&conn), 0);
+
+        // An operation can only be undone once
+        assert!(undo_exec(&conn, Some(1)).is_err());
+
+        // Undo operations themselves cannot be undone
+        let undo_op: i64 = conn
+            .query_row(
+                "SELECT MAX(op_id) FROM audit_log WHERE operation = ?",
+                [AuditOperation::Undo.as_str()],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let error = undo_exec(&conn, Some(undo_op)).unwrap_err();
+        assert!(error.to_string().contains("is an undo"));
+        assert_eq!(count(&conn), 0);
+    }
+
+    #[test]
+    fn test_operation_ids_are_not_reused() {
+        let conn = Connection::open_in_memory().expect("Failed to open database");
+        create_table(&conn, "test_table").unwrap();
+        create_exec(&conn, "test_table", "A", "B", 1.0, 2.0, 3.0).unwrap();
+        create_exec(&conn, "test_table", "C", "D", 1.0, 2.0, 3.0).unwrap();
+        conn.execute("DELETE FROM audit_log", []).unwrap();
+
+        // Ids come from audit_operations, not from the entries still in the log
+        create_exec(&conn, "test_table", "E", "F", 1.0, 2.0, 3.0).unwrap();
+        let op_id: i64 = conn
+            .query_row("SELECT op_id FROM audit_log", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(op_id, 3);
+    }
+
+    #[test]
+    fn test_undo_after_drop() {
+        let conn = Connection::open_in_memory().expect("Failed to open database");
+        create_table(&conn, "kept").unwrap();
+        create_exec(&conn, "kept", "Kept", "TestConfederation", 1.1, 2.2, 3.3).unwrap();
+        create_table(&conn, "dropped").unwrap();
+        create_exec(&conn, "dropped", "X", "Y", 1.0, 2.0, 3.0).unwrap();
+        drop_table(&conn, "dropped").unwrap();
+
+        // The dropped table's insert is skipped, so undo reaches the earlier one
+        undo_exec(&conn, None).unwrap();
+        let kept: i64 = conn
+            .query_row("SELECT COUNT(*) FROM kept", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(kept, 0);
+        undo_exec(&conn, None).unwrap();
+
+        // A table recreated under the same name does not revive the old entries
+        create_table(&conn, "dropped").unwrap();
+        undo_exec(&conn, None).unwrap();
+        assert!(undo_exec(&conn, Some(2)).is_err());
+    }
+
+    #[test]
+    fn test_soft_delete_restore_purge() {
+        let conn = Connection::open_in_memory().expect("Failed to open database");
+        create_table(&conn, "test_table").expect("Failed to create table");
+        for country in ["TestCountry1", "TestCountry2"] {
+            create_exec(
+                &conn,
+                "test_table",
+                country,
+                "TestConfederation",
+                1.1,
+                2.2,
+                3.3,
+            )
+            .unwrap();
+        }
+        let deleted_at = |conn: &Connection, id: i32| -> Option<String> {
+            conn.query_row(
+                "SELECT deleted_at FROM test_table WHERE id = ?",
+                [id],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+
+        soft_delete_exec(&conn, "test_table", 1).unwrap();
+        assert!(deleted_at(&conn, 1).is_some());
+        assert!(deleted_at(&conn, 2).is_none());
+        assert!(read_exec(&conn, "test_table").is_ok());
+
+        restore_exec(&conn, "test_table", 1).unwrap();
+        assert!(deleted_at(&conn, 1).is_none());
+
+        soft_delete_exec(&conn, "test_table", 2).unwrap();
+        purge_exec(&conn, "test_table", None).unwrap();
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM test_table", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+
+        // A purge is audited, so it can still be undone
+        undo_exec(&conn, None).unwrap();
+        assert!(deleted_at(&conn, 2).is_some());
+    }
+
+    #[test]
+    fn test_backup_and_restore() {
+        let backup_path =
+            std::env::temp_dir().join(format!("sqlite_backup_{}.db", std::process::id()));
+        let backup_path = backup_path.to_str().unwrap();
+        let mut conn = Connection::open_in_memory().expect("Failed to open database");
+        create_table(&conn, "test_table").expect("Failed to create table");
+        load_data_from_csv(&conn, "test_table", "data/fifa_countries_audience.csv").unwrap();
+
+        backup_exec(&conn, backup_path, 1).expect("Failed to back up database");
+        drop_table(&conn, "test_table").unwrap();
+        restore_backup_exec(&mut conn, backup_path, 1).expect("Failed to restore database");
+
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM test_table", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 191);
+        std::fs::remove_file(backup_path).unwrap();
+    }
+
+    // Puts the process-wide connection settings back when dropped, even if the
+    // test fails
+    struct SettingsGuard(ConnectionSettings);
+
+    impl Drop for SettingsGuard {
+        fn drop(&mut self) {
+            set_connection_settings(self.0.clone());
+        }
+    }
+
+    #[test]
+    fn test_backup_gives_up_on_lock() {
+        let db_path = std::env::temp_dir().join(format!("sqlite_locked_{}.db", std::process::id()));
+        let backup_path =
+            std::env::temp_dir().join(format!("sqlite_locked_backup_{}.db", std::process::id()));
+        let mut conn = Connection::open(&db_path).unwrap();
+        conn.busy_timeout(Duration::ZERO).unwrap();
+        create_table(&conn, "test_table").unwrap();
+        backup_exec(&conn, backup_path.to_str().unwrap(), 1).unwrap();
+
+        // Restoring waits for the lock only as long as the busy timeout
+        let locker = Connection::open(&db_path).unwrap();
+        locker.execute_batch("BEGIN EXCLUSIVE").unwrap();
+        let guard = SettingsGuard(connection_settings());
+        set_connection_settings(ConnectionSettings {
+            busy_timeout: Some(100),
+            ..Default::default()
+        });
+        let result = restore_backup_exec(&mut conn, backup_path.to_str().unwrap(), 1);
+        drop(guard);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("gave up after 100 ms"));
+
+        locker.execute_batch("ROLLBACK").unwrap();
+        drop(locker);
+        drop(conn);
+        std::fs::remove_file(&db_path).unwrap();
+        std::fs::remove_file(&backup_path).unwrap();
+    }
+
+    #[test]
+    fn test_export_table() {
+        let conn = Connection::open_in_memory().expect("Failed to open database");
+        create_table(&conn, "test_table").expect("Failed to create table");
+        load_data_from_csv(&conn, "test_table", "data/fifa_countries_audience.csv").unwrap();
+
+        // CSV export loads back into an identical table
+        let csv_path =
+            std::env::temp_dir().join(format!("sqlite_export_{}.csv", std::process::id()));
+        let csv_path = csv_path.to_str().unwrap();
+        let mut csv_out = std::fs::File::create(csv_path).unwrap();
+        let count = export_table(&conn, "test_table", ExportFormat::Csv, &mut csv_out).unwrap();
+        assert_eq!(count, 191);
+        create_table(&conn, "test_table_csv").unwrap();
+        load_data_from_csv(&conn, "test_table_csv", csv_path).unwrap();
+        std::fs::remove_file(csv_path).unwrap();
+        let differing: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM (SELECT country, confederation, population_share, tv_audience_share, gdp_weighted_share FROM test_table
+                 EXCEPT SELECT country, confederation, population_share, tv_audience_share, gdp_weighted_share FROM test_table_csv)",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(differing, 0);
+
+        let mut ndjson_out = Vec::new();
+        export_table(&conn, "test_table", ExportFormat::Ndjson, &mut ndjson_out).unwrap();
+        let ndjson_out = String::from_utf8(ndjson_out).unwrap();
+        assert_eq!(ndjson_out.lines().count(), 191);
+        assert!(ndjson_out.starts_with("{\"country\":\"United States\""));
+
+        let mut sql_out = Vec::new();
+        export_table(&conn, "test_table", ExportFormat::Sql, &mut sql_out).unwrap();
+        create_table(&conn, "test_table_copy").unwrap();
+        let dump = String::from_utf8(sql_out)
+            .unwrap()
+            .replace("INSERT INTO test_table ", "INSERT INTO test_table_copy ");
+        conn.execute_batch(&dump).unwrap();
+        let copied: i64 = conn
+            .query_row("SELECT COUNT(*) FROM test_table_copy", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(copied, 191);
+    }
+
+    #[test]
+    fn test_export_round_trip_nulls() {
+        let conn = Connection::open_in_memory().expect("Failed to open database");
+        create_table(&conn, "test_table").unwrap();
+        create_exec(
+            &conn,
+            "test_table",
+            "TestCountry",
+            "TestConfederation",
+            1.1,
+            2.2,
+            3.3,
+        )
+        .unwrap();
+        let fields = UpdateFields {
+            new_confederation: Some(None),
+            new_population_share: Some(None),
+            ..Default::default()
+        };
+        update_exec(&conn, "test_table", 1, fields).unwrap();
+
+        // NULLs export as empty CSV fields and JSON nulls, and load back as NULL
+        for (format, load_format) in [
+            (ExportFormat::Csv, LoadFormat::Csv),
+            (ExportFormat::Ndjson, LoadFormat::Ndjson),
+        ] {
+            let mut out = Vec::new();
+            export_table(&conn, "test_table", format, &mut out).unwrap();
+            create_table(&conn, "test_table_copy").unwrap();
+            let options = LoadOptions {
+                format: load_format,
+                ..Default::default()
+            };
+            load_data_from_reader(&conn, "test_table_copy", out.as_slice(), &options).unwrap();
+            let row: (String, Option<String>, Option<f64>, f64) = conn
+                .query_row(
+                    "SELECT country, confederation, population_share, tv_audience_share FROM test_table_copy",
+                    [],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+                )
+                .unwrap();
+            assert_eq!(row, ("TestCountry".to_string(), None, None, 2.2));
+            drop_table(&conn, "test_table_copy").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_load_data_from_json_and_ndjson() {
+        let conn = Connection::open_in_memory().expect("Failed to open database");
+        create_table(&conn, "test_table").expect("Failed to create table");
+        let dir = std::env::temp_dir();
+        let json_path = dir.join(format!("sqlite_load_{}.json", std::process::id()));
+        let ndjson_path = dir.join(format!("sqlite_load_{}.ndjson", std::process::id()));
+        std::fs::write(
+            &json_path,
+            r#"[{"id": 7, "country": "A", "confederation": "UEFA", "population_share": 0.1, "tv_audience_share": 0.2, "gdp_weighted_share": 0.3},
+                {"country": "B", "confederation": "AFC", "population_share": "1.5", "tv_audience_share": 2, "gdp_weighted_share": 3}]"#,
+        )
+        .unwrap();
+        std::fs::write(
+            &ndjson_path,
+            "{\"country\": \"C\", \"confederation\": \"CAF\", \"population_share\": 1, \"tv_audience_share\": 2, \"gdp_weighted_share\": 3}\n\n",
+        )
+        .unwrap();
+
+        load_data_from_json(&conn, "test_table", json_path.to_str().unwrap()).unwrap();
+        load_data_from_ndjson(&conn, "test_table", ndjson_path.to_str().unwrap()).unwrap();
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM test_table", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 3);
+
+        // A record that fails validation rolls back the whole load
+        std::fs::write(
+            &ndjson_path,
+            "{\"country\": \"D\", \"confederation\": \"CAF\", \"population_share\": 1, \"tv_audience_share\": 2, \"gdp_weighted_share\": 3}\n{\"country\": \"E\"}\n",
+        )
+        .unwrap();
+        assert!(load_data_from_ndjson(&conn, "test_table", ndjson_path.to_str().unwrap()).is_err());
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM test_table", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 3);
+
+        std::fs::remove_file(json_path).unwrap();
+        std::fs::remove_file(ndjson_path).unwrap();
+    }
+
+    #[test]
+    fn test_load_data_from_reader() {
+        let conn = Connection::open_in_memory().expect("Failed to open database");
+        create_table(&conn, "test_table").expect("Failed to create table");
+        let input = "country,confederation,population_share,tv_audience_share,gdp_weighted_share\nA,UEFA,0.1,0.2,0.3\nB,AFC,1.1,1.2,1.3\n";
+        let count = load_data_from_reader(
+            &conn,
+            "test_table",
+            input.as_bytes(),
+            &LoadOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn test_load_data_with_csv_dialect() {
+        let conn = Connection::open_in_memory().expect("Failed to open database");
+        create_table(&conn, "test_table").expect("Failed to create table");
+        let input =
+            "\u{feff}# exported 2024-01-01\n'Côte d''Ivoire';CAF; 0,5 ; 1,25 ;2\nB;AFC;1;2;3\n";
+        let options = LoadOptions {
+            format: LoadFormat::Csv,
+            csv: CsvOptions {
+                delimiter: b';',
+                has_headers: false,
+                quote: b'\'',
+                comment: Some(b'#'),
+                trim: true,
+                decimal_separator: ',',
+                strip_bom: true,
+            },
+        };
+        let count = load_data_from_reader(&conn, "test_table", input.as_bytes(), &options).unwrap();
+        assert_eq!(count, 2);
+        let (country, tv_audience_share): (String, f64) = conn
+            .query_row(
+                "SELECT country, tv_audience_share FROM test_table WHERE id = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(country, "Côte d'Ivoire");
+        assert_eq!(tv_audience_share, 1.25);
+
+        // A kept byte order mark hides the comment marker on the first line
+        let mut keep_bom = options.clone();
+        keep_bom.csv.strip_bom = false;
+        let result = load_data_from_reader(&conn, "test_table", input.as_bytes(), &keep_bom);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("expected 5 columns"));
+
+        // A comma cannot separate both fields and decimals
+        let comma = CsvOptions {
+            decimal_separator: ',',
+            ..CsvOptions::default()
+        };
+        assert!(comma.validate().is_err());
+        assert!(options.csv.validate().is_ok());
+    }
+
+    #[test]
+    fn test_load_compressed_data() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let conn = Connection::open_in_memory().expect("Failed to open database");
+        create_table(&conn, "test_table").expect("Failed to create table");
+        let csv = std::fs::read("data/fifa_countries_audience.csv").unwrap();
+
+        let mut gzip = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(&csv).unwrap();
+        let gzip = gzip.finish().unwrap();
+
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip.add_directory("snapshots/", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.start_file(
+            "snapshots/fifa_countries_audience.csv",
+            zip::write::SimpleFileOptions::default(),
+        )
+        .unwrap();
+        zip.write_all(&csv).unwrap();
+        let zip = zip.finish().unwrap().into_inner();
+
+        for compressed in [gzip, zip] {
+            let count = load_data_from_reader(
+                &conn,
+                "test_table",
+                compressed.as_slice(),
+                &LoadOptions::default(),
+            )
+            .unwrap();
+            assert_eq!(count, 191);
+
+            // A pipe may return the magic bytes over several reads
+            let count = load_data_from_reader(
+                &conn,
+                "test_table",
+                OneByteReader(compressed.as_slice()),
+                &LoadOptions::default(),
+            )
+            .unwrap();
+            assert_eq!(count, 191);
+        }
+    }
+
+    // Returns at most one byte per read, like a slow pipe
+    struct OneByteReader<'a>(&'a [u8]);
+
+    impl std::io::Read for OneByteReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(1);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_load_format_from_path() {
+        assert_eq!(
+            LoadFormat::from_path("a.ndjson.gz"),
+            Some(LoadFormat::Ndjson)
+        );
+        assert_eq!(LoadFormat::from_path("a.json"), Some(LoadFormat::Json));
+        assert_eq!(LoadFormat::from_path("a.zip"), None);
+    }
+
+    #[test]
+    fn test_extract_file_path() {
+        assert_eq!(
+            extract_file_path("https://example.com/archive/snapshot.csv.gz"),
+            "data/snapshot.csv"
+        );
+        assert_eq!(
+            extract_file_path("https://example.com/snapshot.zip?raw=true"),
+            "data/snapshot.csv"
+        );
+    }
+
+    #[test]
+    fn test_load_generic_csv() {
+        let conn = Connection::open_in_memory().expect("Failed to open database");
+        let csv_path =
+            std::env::temp_dir().join(format!("sqlite_generic_{}.csv", std::process::id()));
+        std::fs::write(
+            &csv_path,
+            "Name,Year,Rating (%),ID,Notes\nAlpha,2014,12.5,1,\nBeta,2018,7,2,late\n",
+        )
+        .unwrap();
+
+        let columns = load_generic_csv(
+            &conn,
+            "test_generic",
+            csv_path.to_str().unwrap(),
+            &CsvOptions::default(),
+            100,
+        )
+        .unwrap();
+        std::fs::remove_file(&csv_path).unwrap();
+
+        let schema: Vec<(&str, ColumnType)> = columns
+            .iter()
+            .map(|c| (c.name.as_str(), c.column_type))
+            .collect();
+        assert_eq!(
+            schema,
+            vec![
+                ("name", ColumnType::Text),
+                ("year", ColumnType::Integer),
+                ("rating", ColumnType::Real),
+                ("id_2", ColumnType::Integer),
+                ("notes", ColumnType::Text),
+            ]
+        );
+        let (year, notes): (i64, Option<String>) = conn
+            .query_row(
+                "SELECT year, notes FROM test_generic WHERE name = 'Alpha'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(year, 2014);
+        assert_eq!(notes, None);
+        assert!(read_exec(&conn, "test_generic").is_ok());
+    }
+
+    #[test]
+    fn test_load_generic_csv_failures() {
+        let conn = Connection::open_in_memory().expect("Failed to open database");
+        let csv_path =
+            std::env::temp_dir().join(format!("sqlite_generic_bad_{}.csv", std::process::id()));
+        let csv_path_str = csv_path.to_str().unwrap();
+        let table_exists = |conn: &Connection| {
+            conn.query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE name = 'test_generic'",
+                [],
+                |row| row.get::<_, i64>(0),
+            )
+            .unwrap()
+                == 1
+        };
+
+        // An empty file has no header to build a table from
+        std::fs::write(&csv_path, "").unwrap();
+        let result = load_generic_csv(
+            &conn,
+            "test_generic",
+            csv_path_str,
+            &CsvOptions::default(),
+            1,
+        );
+        assert!(result.unwrap_err().to_string().contains("no columns"));
+        assert!(!table_exists(&conn));
+
+        // A bad record after the sample takes back the table as well as the rows
+        std::fs::write(&csv_path, "name,year\nAlpha,2014\nBeta\n").unwrap();
+        let result = load_generic_csv(
+            &conn,
+            "test_generic",
+            csv_path_str,
+            &CsvOptions::default(),
+            1,
+        );
+        assert!(result.unwrap_err().to_string().contains("record 2"));
+        assert!(!table_exists(&conn));
+
+        std::fs::remove_file(&csv_path).unwrap();
+    }
+
+    #[test]
+    fn test_run_sql() {
+        let conn = Connection::open_in_memory().expect("Failed to open database");
+        create_table(&conn, "test_table").unwrap();
+        create_exec(&conn, "test_table", "Alpha", "UEFA", 1.5, 2.0, 3.0).unwrap();
+        create_exec(&conn, "test_table", "Beta", "CAF", 0.5, 1.0, 1.5).unwrap();
+
+        let params = QueryParams {
+            positional: vec![parse_param_value("1")],
+            named: vec![parse_named_param("conf=UEFA").unwrap()],
+        };
+        let query =
+            "SELECT country, population_share FROM test_table WHERE confederation = :conf LIMIT ?";
+        let mut out = Vec::new();
+        let count = run_sql(&conn, query, &params, OutputFormat::Ndjson, &mut out).unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"country\":\"Alpha\",\"population_share\":1.5}\n"
+        );
+
+        let mut out = Vec::new();
+        let query = "SELECT country FROM test_table ORDER BY id";
+        run_sql(
+            &conn,
+            query,
+            &QueryParams::default(),
+            OutputFormat::Csv,
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "country\nAlpha\nBeta\n");
+
+        // Unbound and unknown parameters are errors rather than silent NULLs
+        let query = "SELECT * FROM test_table WHERE id = ?";
+        assert!(run_sql(
+            &conn,
+            query,
+            &QueryParams::default(),
+            OutputFormat::Table,
+            &mut Vec::new()
+        )
+        .is_err());
+        let params = QueryParams {
+            positional: vec![],
+            named: vec![("missing".to_string(), parse_param_value("NULL"))],
+        };
+        assert!(run_sql(
+            &conn,
+            "SELECT :id",
+            &params,
+            OutputFormat::Json,
+            &mut Vec::new()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_script() {
+        let script = parse_script(
+            "# setup\ncreate t1\n\n  load t1 data.csv  \n-- check\n--\n",
+            ScriptFormat::Lines,
+        )
+        .unwrap();
+        assert_eq!(script.commands, vec!["create t1", "load t1 data.csv"]);
+        assert!(!script.transaction);
+        assert_eq!(script.on_error, OnError::Stop);
+
+        let toml = "transaction = true\non_error = \"continue\"\ncommands = [\"create t1\"]\n";
+        let script = parse_script(toml, ScriptFormat::Toml).unwrap();
+        assert!(script.transaction);
+        assert_eq!(script.on_error, OnError::Continue);
+        assert_eq!(script.commands, vec!["create t1"]);
+
+        assert_eq!(ScriptFormat::from_path("setup.TOML"), ScriptFormat::Toml);
+        assert_eq!(ScriptFormat::from_path("setup.txt"), ScriptFormat::Lines);
+        let e = read_script("setup.yml").unwrap_err();
+        assert!(e.to_string().contains("YAML is not supported"));
+    }
+
+    #[test]
+    fn test_run_pipeline() {
+        let conn = Connection::open_in_memory().expect("Failed to open database");
+        let csv_path =
+            std::env::temp_dir().join(format!("sqlite_pipeline_{}.csv", std::process::id()));
+        std::fs::write(
+            &csv_path,
+            "country,confederation,population_share,tv_audience_share,gdp_weighted_share\n\
+             Alpha,UEFA,1.5,2.0,3.0\nBeta,CAF,0.5,1.0,1.5\n",
+        )
+        .unwrap();
+        let definition = format!(
+            "table = \"test_table\"\n\
+             transform = [\"CREATE TABLE test_summary AS SELECT COUNT(*) AS n FROM test_table\"]\n\
+             [source]\npath = {:?}\n\
+             [validate]\nmin_rows = 2\nunique = [\"country\"]\nchecks = [\"population_share < 2\"]\n",
+            csv_path.to_str().unwrap()
+        );
+
+        let pipeline = parse_pipeline(&definition).unwrap();
+        let reports = run_pipeline(&conn, &pipeline);
+        let steps: Vec<&str> = reports.iter().map(|r| r.step.as_str()).collect();
+        assert_eq!(
+            steps,
+            vec!["extract", "create", "load", "validate", "transform 1"]
+        );
+        assert!(matches!(reports[0].status, StepStatus::Skipped(_)));
+        assert!(reports[1..].iter().all(|r| r.status == StepStatus::Ok));
+        let n: i64 = conn
+            .query_row("SELECT n FROM test_summary", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(n, 2);
+
+        // A failed rule skips the transforms and rolls the load back
+        let failing = definition.replace("min_rows = 2", "min_rows = 10");
+        let reports = run_pipeline(&conn, &parse_pipeline(&failing).unwrap());
+        std::fs::remove_file(&csv_path).unwrap();
+        assert!(matches!(reports[3].status, StepStatus::Failed(_)));
+        assert!(matches!(reports[4].status, StepStatus::Skipped(_)));
+        let rows: i64 = conn
+            .query_row("SELECT COUNT(*) FROM test_table", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rows, 2);
+    }
+
+    #[test]
+    fn test_profiler() {
+        let conn = Connection::open_in_memory().expect("Failed to open database");
+        create_table(&conn, "test_table").expect("Failed to create table");
+        let input = "country,confederation,population_share,tv_audience_share,gdp_weighted_share\nA,UEFA,0.1,0.2,0.3\nB,AFC,1.1,1.2,1.3\n";
+
+        let profiler = Profiler::start();
+        load_data_from_reader(
+            &conn,
+            "test_table",
+            input.as_bytes(),
+            &LoadOptions::default(),
+        )
+        .unwrap();
+        let profile = profiler.finish("load");
+        assert_eq!(profile.command, "load");
+        // Other tests running in parallel may add to the shared row counter
+        assert!(profile.rows >= 2);
+        assert!(profile.wall_seconds > 0.0);
+        assert!(profile.peak_rss_kb >= profile.rss_after_kb);
+
+        let mut out = Vec::new();
+        write_profiles(
+            std::slice::from_ref(&profile),
+            ProfileFormat::Csv,
+            true,
+            &mut out,
+        )
+        .unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "command,started_at,wall_seconds,cpu_seconds,rss_before_kb,rss_after_kb,peak_rss_kb,rows"
+        );
+        assert!(lines.next().unwrap().starts_with("load,"));
+
+        let mut out = Vec::new();
+        write_profiles(&[profile], ProfileFormat::Json, false, &mut out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json["command"], "load");
+    }
+
+    #[test]
+    fn test_memory_sampler() {
+        let sampler = MemorySampler::start(Duration::from_millis(1));
+        // Touch every page so the allocation counts towards resident memory
+        let buffer = vec![1u8; 32 * 1024 * 1024];
+        std::thread::sleep(Duration::from_millis(20));
+        drop(buffer);
+        let samples = sampler.stop();
+
+        assert!(samples.len() > 2);
+        assert!(samples
+            .windows(2)
+            .all(|pair| pair[0].elapsed_ms <= pair[1].elapsed_ms));
+        let first = samples[0].rss_kb;
+        let peak = samples.iter().map(|s| s.rss_kb).max().unwrap();
+        assert!(peak >= first + 16 * 1024);
+    }
+
+    #[test]
+    fn test_report() {
+        let profile = |command: &str, wall_seconds: f64| CommandProfile {
+            command: command.to_string(),
+            started_at: 0,
+            wall_seconds,
+            cpu_seconds: wall_seconds / 2.0,
+            rss_before_kb: 1024,
+            rss_after_kb: 2048,
+            peak_rss_kb: 4096,
+            rows: 191,
+            rss_samples: Vec::new(),
+        };
+        let profiles = vec![
+            profile("load t1 data.csv", 0.010),
+            profile("query t1", 0.002),
+            profile("load t1 data.csv", 0.020),
+        ];
+        let summaries = summarize(&profiles);
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].runs, 2);
+        assert!((summaries[0].mean_wall_seconds - 0.015).abs() < 1e-9);
+        assert_eq!(summaries[0].min_wall_seconds, 0.010);
+
+        let baseline = summarize(&[profile("load t1 data.csv", 0.030)]);
+        let mut out = Vec::new();
+        render_report(
+            &Environment::collect(),
+            &summaries,
+            Some(&baseline),
+            &mut out,
+        )
+        .unwrap();
+        let report = String::from_utf8(out).unwrap();
+        assert!(report.starts_with("# Performance Report"));
+        assert!(report.contains("| `query t1` | 1 | 2.00 |"));
+        assert!(report.contains("| `load t1 data.csv` | 30.00 | 15.00 | -50.0% |"));
+    }
+
+    #[test]
+    fn test_compare_tables() {
+        // Stand-in for the FifaDB.db the Python loader writes
+        let python_db =
+            std::env::temp_dir().join(format!("sqlite_compare_python_{}.db", std::process::id()));
+        let python_db = python_db.to_str().unwrap();
+        let python = Connection::open(python_db).unwrap();
+        python
+            .execute_batch(&format!(
+                "DROP TABLE IF EXISTS {0};
+                 CREATE TABLE {0} (id INTEGER PRIMARY KEY AUTOINCREMENT, country TEXT,
+                     confederation TEXT, population_share REAL, tv_audience_share REAL,
+                     gdp_weighted_share REAL);
+                 INSERT INTO {0} (country, confederation, population_share,
+                     tv_audience_share, gdp_weighted_share)
+                 VALUES ('A', 'UEFA', 0.1, 0.2, 0.3), ('B', 'AFC', 1.5, 2.5, 3.5);",
+                PYTHON_TABLE
+            ))
+            .unwrap();
+
+        let csv_path =
+            std::env::temp_dir().join(format!("sqlite_compare_{}.csv", std::process::id()));
+        let csv_path = csv_path.to_str().unwrap();
+        std::fs::write(
+            csv_path,
+            "country,confederation,population_share,tv_audience_share,gdp_weighted_share\n\
+             A,UEFA,0.1,0.2,0.3\nB,AFC,1.5,2.5,3.5\n",
+        )
+        .unwrap();
+        let conn = Connection::open_in_memory().unwrap();
+        time_rust_load(&conn, "rust_load", csv_path).unwrap();
+        let comparison = compare_tables(&conn, "rust_load", python_db, PYTHON_TABLE).unwrap();
+        assert!(comparison.is_match());
+        assert_eq!(comparison.rust_rows, 2);
+
+        python
+            .execute(
+                &format!(
+                    "UPDATE {} SET population_share = 9 WHERE country = 'B'",
+                    PYTHON_TABLE
+                ),
+                [],
+            )
+            .unwrap();
+        let comparison = compare_tables(&conn, "rust_load", python_db, PYTHON_TABLE).unwrap();
+        assert!(!comparison.is_match());
+        assert_eq!((comparison.only_in_rust, comparison.only_in_python), (1, 1));
+        let sum = comparison
+            .aggregates
+            .iter()
+            .find(|a| a.name == "SUM(population_share)")
+            .unwrap();
+        assert!(!sum.matches());
+
+        drop(python);
+        std::fs::remove_file(python_db).unwrap();
+        std::fs::remove_file(csv_path).unwrap();
+    }
+
+    #[test]
+    fn test_generate_rows() {
+        let generate = |rows: usize, seed: u64| {
+            let mut out = Vec::new();
+            generate_rows(rows, seed, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let data = generate(500, 7);
+        assert_eq!(data, generate(500, 7));
+        assert_ne!(data, generate(500, 8));
+
+        let mut rdr = csv::Reader::from_reader(data.as_bytes());
+        assert_eq!(
+            rdr.headers().unwrap(),
+            vec![
+                "country",
+                "confederation",
+                "population_share",
+                "tv_audience_share",
+                "gdp_weighted_share"
+            ]
+        );
+        let records: Vec<_> = rdr.records().map(|r| r.unwrap()).collect();
+        assert_eq!(records.len(), 500);
+        for record in &records {
+            assert!(["AFC", "CAF", "CONCACAF", "CONMEBOL", "OFC", "UEFA"].contains(&&record[1]));
+            for share in [&record[2], &record[3], &record[4]] {
+                assert!(share.parse::<f64>().unwrap() >= 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_for_each_country() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_table(&conn, "stream_table").unwrap();
+        for i in 1..=5 {
+            create_exec(
+                &conn,
+                "stream_table",
+                &format!("Country{}", i),
+                "UEFA",
+                0.1,
+                0.2,
+                0.3,
+            )
+            .unwrap();
+        }
+        soft_delete_exec(&conn, "stream_table", 2).unwrap();
+
+        let mut seen: Vec<FifaCountry> = Vec::new();
+        let visited = for_each_country(&conn, "stream_table", false, |record| {
+            seen.push(record);
+            if seen.len() < 3 {
+                ControlFlow::Continue(())
+            } else {
+                ControlFlow::Break(())
+            }
+        })
+        .unwrap();
+        assert_eq!(visited, 3);
+        let ids: Vec<i32> = seen.iter().map(|record| record.id).collect();
+        assert_eq!(ids, vec![1, 3, 4]);
+        assert_eq!(seen[0].country.as_deref(), Some("Country1"));
+        assert!(seen[0].to_string().starts_with("ID: 1, Country: Country1"));
+
+        let mut deleted = Vec::new();
+        let visited = for_each_country(&conn, "stream_table", true, |record| {
+            deleted.extend(record.deleted_at);
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        assert_eq!(visited, 5);
+        assert_eq!(deleted.len(), 1);
+    }
+
+    #[test]
+    fn test_connection_settings() {
+        let from_file: ConnectionSettings =
+            toml::from_str("journal_mode = \"wal\"\nsynchronous = \"normal\"\ncache_size = -2000")
+                .unwrap();
+        let settings = from_file.merge(&ConnectionSettings {
+            synchronous: Some(Synchronous::Off),
+            busy_timeout: Some(250),
+            ..ConnectionSettings::default()
+        });
+        assert_eq!(settings.journal_mode, Some(JournalMode::Wal));
+        assert_eq!(settings.synchronous, Some(Synchronous::Off));
+        assert_eq!(settings.cache_size, Some(-2000));
+
+        let path = std::env::temp_dir().join(format!(
+            "sqlite_connection_settings_{}.db",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        let conn = Connection::open(path).unwrap();
+        settings.apply(&conn).unwrap();
+        let pragma = |name: &str| -> rusqlite::types::Value {
+            conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(pragma("journal_mode"), "wal".to_string().into());
+        assert_eq!(pragma("synchronous"), 0.into());
+        assert_eq!(pragma("cache_size"), (-2000).into());
+        assert_eq!(pragma("busy_timeout"), 250.into());
+
+        drop(conn);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path, suffix));
+        }
+    }
+
+    #[test]
+    fn test_config_layers() {
+        let temp_path = |name: &str| {
+            std::env::temp_dir().join(format!(
+                "sqlite_config_{}_{}.toml",
+                name,
+                std::process::id()
+            ))
+        };
+        let user = temp_path("user");
+        let project = temp_path("project");
+        std::fs::write(
+            &user,
+            "table = \"t_user\"\noutput_format = \"csv\"\n[connection]\nbusy_timeout = 100\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &project,
+            "table = \"t_project\"\ndatabase = \"project.db\"\n",
+        )
+        .unwrap();
+        let missing = temp_path("missing");
+
+        let mut config = Config::load(&[user.clone(), project.clone(), missing]).unwrap();
+        assert_eq!(config.table.value.as_deref(), Some("t_project"));
+        assert_eq!(config.table.origin, Origin::File(project.clone()));
+        assert_eq!(config.output_format.value, OutputFormat::Csv);
+        assert_eq!(config.busy_timeout.origin, Origin::File(user.clone()));
+        assert_eq!(config.source_url.origin, Origin::Default);
+
+        config
+            .database
+            .set(Some("flag.db".to_string()), &Origin::Flag);
+        config.database.set(None, &Origin::Default);
+        assert_eq!(config.database.value, "flag.db");
+        assert_eq!(config.connection_settings().busy_timeout, Some(100));
+        let (key, value, origin) = &config.entries()[0];
+        assert_eq!((*key, value.as_str()), ("database", "flag.db"));
+        assert_eq!(origin.to_string(), "command line");
+
+        std::fs::write(&project, "unknown_key = 1\n").unwrap();
+        assert!(Config::load(std::slice::from_ref(&project)).is_err());
+        std::fs::remove_file(user).unwrap();
+        std::fs::remove_file(project).unwrap();
+    }
+
+    #[test]
+    fn test_describe_table() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_table(&conn, "schema_table").unwrap();
+        for country in ["A", "B", "C"] {
+            create_exec(&conn, "schema_table", country, "UEFA", 0.1, 0.2, 0.3).unwrap();
+        }
+        soft_delete_exec(&conn, "schema_table", 2).unwrap();
+        conn.execute_batch("CREATE UNIQUE INDEX schema_country ON schema_table(country)")
+            .unwrap();
+
+        let tables = list_tables(&conn).unwrap();
+        let table = tables.iter().find(|t| t.name == "schema_table").unwrap();
+        assert_eq!(table.kind, "table");
+        assert_eq!(table.columns, 7);
+
+        let description = describe_table(&conn, "schema_table").unwrap();
+        assert_eq!(description.columns[0].name, "id");
+        assert_eq!(description.columns[0].primary_key, 1);
+        assert_eq!(description.columns[1].data_type, "TEXT");
+        assert_eq!(description.indexes.len(), 1);
+        assert!(description.indexes[0].unique);
+        assert_eq!(description.indexes[0].columns, vec!["country"]);
+        assert_eq!((description.rows, description.deleted_rows), (2, 1));
+
+        assert_eq!(count_table_rows(&conn, "schema_table", false).unwrap(), 2);
+        assert_eq!(count_table_rows(&conn, "schema_table", true).unwrap(), 3);
+        assert!(count_table_rows(&conn, "missing_table", false).is_err());
+    }
 }